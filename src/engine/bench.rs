@@ -0,0 +1,39 @@
+//! A fixed set of positions searched to a fixed depth, for comparing the node counts and
+//! speed of different versions and search parameters.
+
+use crate::game::board::Board;
+
+use super::{
+    search::{iterative_deepening, EvalResult, SearchParams},
+    transposition::TranspositionTable,
+};
+
+/// the depth every bench position is searched to
+pub const BENCH_DEPTH: u8 = 6;
+
+/// openings, middlegames with tactics and a few endgames
+pub const BENCH_POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3",
+    "2kr3r/ppp2ppp/2n5/2b1p3/4P1q1/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 10",
+];
+
+/// searches every bench position to `depth`, calling `report` with each one's result,
+/// and returns the total number of nodes
+pub fn bench(depth: u8, params: SearchParams, mut report: impl FnMut(&str, &EvalResult)) -> u64 {
+    let tt = TranspositionTable::default();
+    let mut nodes = 0;
+    for fen in BENCH_POSITIONS {
+        tt.clear();
+        let mut board = Board::new(fen);
+        let result = iterative_deepening(&mut board, depth, params, &tt, |_| ());
+        report(fen, &result);
+        nodes += result.nodes;
+    }
+    nodes
+}
@@ -1,21 +1,50 @@
-use crate::game::board::{Board, PieceTypes};
+//! Static evaluation.
+
+use crate::game::{board::{Board, PieceTypes}, square::Square};
 use super::piecemaps;
 
-pub fn evaluate(board: &Board) -> f32 {
-    let mut eval: f32 = 0.0;
-
-    let mut square: usize = 0;
-    for piece in board.board {
-        eval += match piece.piece_type {        
-            PieceTypes::Pawn => {if piece.white {1.0 * piecemaps::WHITE_PAWN_MAP[square]} else {-1.0 * piecemaps::BLACK_PAWN_MAP[square]}},
-            PieceTypes::Bishop => {if piece.white {3.0 * piecemaps::KNIGHT_MAP[square]} else {-3.0 * piecemaps::KNIGHT_MAP[square]}},
-            PieceTypes::Knight => {if piece.white {3.0} else {-3.0}},
-            PieceTypes::Rook => {if piece.white {5.0} else {-5.0}},
-            PieceTypes::Queen => {if piece.white {9.0} else {-9.0}},
-            _ => {0.0},
-        };
-        square += 1;
+/// centipawns from the side to move's point of view
+pub type Score = i32;
+
+/// static evaluation from the side to move's point of view
+pub fn evaluate(board: &Board) -> Score {
+    let mut eval: Score = 0;
+
+    for square in Square::all() {
+        let piece = board.board[square.index()];
+        // the maps are drawn for white, so black looks at them from the other side
+        let map_square = if piece.color.is_white() {square} else {square.flip()}.index();
+        let value = match piece.piece_type {
+            PieceTypes::Pawn => 100.0 * piecemaps::PAWN_MAP[map_square],
+            PieceTypes::Bishop => 300.0 * piecemaps::KNIGHT_MAP[map_square],
+            PieceTypes::Knight => 300.0,
+            PieceTypes::Rook => 500.0,
+            PieceTypes::Queen => 900.0,
+            _ => 0.0,
+        }.round() as Score;
+        eval += if piece.color.is_white() {value} else {-value};
     }
 
-    eval * if board.wtomove {1.0} else {-1.0}
-}
\ No newline at end of file
+    if board.side_to_move.is_white() {eval} else {-eval}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+    use crate::game::board::{Board, STARTPOS};
+
+    #[test]
+    fn evaluate_is_symmetric() {
+        for fen in [
+            STARTPOS,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        ] {
+            let board = Board::new(fen);
+            assert_eq!(evaluate(&board), evaluate(&board.mirror()), "{}", fen);
+        }
+    }
+}
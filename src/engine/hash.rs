@@ -1,71 +1,185 @@
-use rand::prelude::random;
-
-use crate::game::board::Board;
-
-pub const WPAWN: usize = 0;
-pub const BPAWN: usize = 1;
-pub const WBISHOP: usize = 2;
-pub const BBISHOP: usize = 3;
-pub const WKNIGHT: usize = 4;
-pub const BKNIGHT: usize = 5;
-pub const WROOK: usize = 6;
-pub const BROOK: usize = 7;
-pub const WQUEEN: usize = 8;
-pub const BQUEEN: usize = 9;
-pub const WKING: usize = 10;
-pub const BKING: usize = 11;
-
-pub fn init_table() -> [[u64; 12]; 64] {
-    let mut table = [[0u64; 12]; 64];
-    for i in 0..64 {
-        for j in 0..12 {
-            table[i][j] = random();
+//! Zobrist hashing.
+
+use crate::game::{
+    board::{Board, Piece, PieceTypes},
+    color::Color,
+};
+
+pub(crate) const WPAWN: usize = 0;
+pub(crate) const BPAWN: usize = 1;
+pub(crate) const WBISHOP: usize = 2;
+pub(crate) const BBISHOP: usize = 3;
+pub(crate) const WKNIGHT: usize = 4;
+pub(crate) const BKNIGHT: usize = 5;
+pub(crate) const WROOK: usize = 6;
+pub(crate) const BROOK: usize = 7;
+pub(crate) const WQUEEN: usize = 8;
+pub(crate) const BQUEEN: usize = 9;
+pub(crate) const WKING: usize = 10;
+pub(crate) const BKING: usize = 11;
+
+/// the random numbers hashes are made of. they come from a fixed seed, so the same
+/// position has the same hash on every board and in every run
+pub struct ZobristKeys {
+    /// by square index and piece_index
+    pub pieces: [[u64; 12]; 64],
+    /// xored in when black is to move
+    pub side_to_move: u64,
+    /// white king side, white queen side, black king side, black queen side
+    pub castling: [u64; 4],
+    /// by the file of the en passant square
+    pub enpassant: [u64; 8],
+}
+
+/// the keys every hash in the program is made of
+pub static ZOBRIST: ZobristKeys = generate_keys(0x2545_f491_4f6c_dd1d);
+
+// splitmix64, returning the next number and the new state
+const fn next_random(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31), state)
+}
+
+const fn generate_keys(seed: u64) -> ZobristKeys {
+    let mut keys = ZobristKeys {
+        pieces: [[0; 12]; 64],
+        side_to_move: 0,
+        castling: [0; 4],
+        enpassant: [0; 8],
+    };
+    let mut state = seed;
+    let mut random;
+
+    let mut square = 0;
+    while square < 64 {
+        let mut piece = 0;
+        while piece < 12 {
+            (random, state) = next_random(state);
+            keys.pieces[square][piece] = random;
+            piece += 1;
         }
+        square += 1;
     }
-
-    table
+    (keys.side_to_move, state) = next_random(state);
+    let mut i = 0;
+    while i < 4 {
+        (random, state) = next_random(state);
+        keys.castling[i] = random;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 8 {
+        (random, state) = next_random(state);
+        keys.enpassant[i] = random;
+        i += 1;
+    }
+    keys
 }
 
+/// the zobrist table column of a piece, one of the constants above
+pub fn piece_index(piece: &Piece) -> usize {
+    match (piece.piece_type, piece.color) {
+        (PieceTypes::Pawn, Color::White) => WPAWN,
+        (PieceTypes::Pawn, Color::Black) => BPAWN,
+        (PieceTypes::Bishop, Color::White) => WBISHOP,
+        (PieceTypes::Bishop, Color::Black) => BBISHOP,
+        (PieceTypes::Knight, Color::White) => WKNIGHT,
+        (PieceTypes::Knight, Color::Black) => BKNIGHT,
+        (PieceTypes::Rook, Color::White) => WROOK,
+        (PieceTypes::Rook, Color::Black) => BROOK,
+        (PieceTypes::Queen, Color::White) => WQUEEN,
+        (PieceTypes::Queen, Color::Black) => BQUEEN,
+        (_, Color::White) => WKING,
+        (_, Color::Black) => BKING,
+    }
+}
 
+/// the zobrist hash of the position from scratch, push keeps it up to date incrementally
 pub fn generate_hash(board: &Board) -> u64 {
     let mut hash = 0u64;
-    let mut curr_square: usize = 0;
-    for piece in board.board {
-        match piece.piece_type {
-            crate::game::board::PieceTypes::Empty => (),
-            crate::game::board::PieceTypes::Pawn => if piece.white {
-                hash ^= board.zobrist_table[curr_square][WPAWN];
-            } else {
-                hash ^= board.zobrist_table[curr_square][BPAWN];
-            },
-            crate::game::board::PieceTypes::Bishop => if piece.white {
-                hash ^= board.zobrist_table[curr_square][WBISHOP];
-            } else {
-                hash ^= board.zobrist_table[curr_square][BBISHOP];
-            },
-            crate::game::board::PieceTypes::Knight => if piece.white {
-                hash ^= board.zobrist_table[curr_square][WKNIGHT];
-            } else {
-                hash ^= board.zobrist_table[curr_square][BKNIGHT];
-            },
-            crate::game::board::PieceTypes::Rook => if piece.white {
-                hash ^= board.zobrist_table[curr_square][WROOK];
-            } else {
-                hash ^= board.zobrist_table[curr_square][BROOK];
-            },
-            crate::game::board::PieceTypes::Queen => if piece.white {
-                hash ^= board.zobrist_table[curr_square][WQUEEN];
-            } else {
-                hash ^= board.zobrist_table[curr_square][BQUEEN];
-            },
-            crate::game::board::PieceTypes::King => if piece.white {
-                hash ^= board.zobrist_table[curr_square][WKING];
-            } else {
-                hash ^= board.zobrist_table[curr_square][BKING];
-            },
+    for (square, piece) in board.board.iter().enumerate() {
+        if piece.piece_type != PieceTypes::Empty {
+            hash ^= ZOBRIST.pieces[square][piece_index(piece)];
+        }
+    }
+
+    if board.side_to_move == Color::Black {
+        hash ^= ZOBRIST.side_to_move;
+    }
+    let rights = [board.white_ks, board.white_qs, board.black_ks, board.black_qs];
+    for (right, key) in rights.iter().zip(ZOBRIST.castling) {
+        if right.is_some() {
+            hash ^= key;
         }
-        curr_square += 1;
+    }
+    if let Some(square) = board.enpassant_square {
+        hash ^= ZOBRIST.enpassant[square.file().index()];
     }
 
     hash
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_hash;
+    use crate::game::board::{Board, STARTPOS};
+    use crate::game::movegen::generate_legal_moves;
+
+    // the incremental hash matches a fresh one after every move and null move, and taking
+    // either back restores the position exactly
+    fn assert_hashes(board: &mut Board, depth: u8) {
+        assert_eq!(board.hash, generate_hash(board), "{}", board.fen());
+        let fen = board.fen();
+        let (hash, enpassant, side) = (board.hash, board.enpassant_square, board.side_to_move);
+        let unchanged = |board: &Board| {
+            assert_eq!(board.hash, hash);
+            assert_eq!(board.enpassant_square, enpassant);
+            assert_eq!(board.side_to_move, side);
+            assert_eq!(board.fen(), fen);
+        };
+
+        let undo = board.make_null_move();
+        assert_eq!(board.hash, generate_hash(board), "null move in {}", fen);
+        assert_eq!(board.enpassant_square, None);
+        board.unmake_null_move(undo);
+        unchanged(board);
+
+        if depth == 0 {
+            return;
+        }
+        for m in generate_legal_moves(board) {
+            let undo = board.push(&m);
+            assert_hashes(board, depth - 1);
+            undo(board);
+            unchanged(board);
+        }
+    }
+
+    #[test]
+    fn incremental_hash() {
+        for (fen, depth) in [
+            (STARTPOS, 3),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 2),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 2),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 2),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 2),
+        ] {
+            assert_hashes(&mut Board::new(fen), depth);
+        }
+    }
+
+    #[test]
+    fn same_position_same_hash() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(Board::new(fen).hash, Board::new(fen).hash);
+        assert_ne!(Board::new(fen).hash, Board::new(STARTPOS).hash);
+
+        let board = Board::new(STARTPOS);
+        let decoded = Board::decode(&board.encode().unwrap()).unwrap();
+        assert_eq!(decoded.hash, board.hash);
+    }
+}
@@ -0,0 +1,97 @@
+//! Butterfly history, how often a quiet move has caused a cutoff anywhere in the tree.
+
+use crate::game::{board::Move, color::Color};
+
+/// scores stay between -MAX_HISTORY and MAX_HISTORY
+pub const MAX_HISTORY: i32 = 16384;
+
+/// indexed by the side that moved and the move's from and to squares
+pub struct History {
+    table: Box<[[[i32; 64]; 64]; 2]>,
+}
+
+impl History {
+    /// every move starts at 0
+    pub fn new() -> History {
+        History {
+            table: Box::new([[[0; 64]; 64]; 2]),
+        }
+    }
+
+    /// the score of `m` played by `color`, higher for moves that caused more cutoffs
+    pub fn get(&self, color: Color, m: &Move) -> i32 {
+        self.table[color.index()][m.from().index()][m.to().index()]
+    }
+
+    /// a positive bonus for a move that caused a cutoff, a negative one for the quiet moves
+    /// that were tried before it. the closer an entry is to the limit the less it moves
+    /// (gravity), so old results fade as new ones come in
+    pub fn update(&mut self, color: Color, m: &Move, bonus: i32) {
+        let bonus = bonus.clamp(-MAX_HISTORY, MAX_HISTORY);
+        let entry = &mut self.table[color.index()][m.from().index()][m.to().index()];
+        *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// deeper cutoffs say more about a move
+pub fn history_bonus(depth: u8) -> i32 {
+    (depth as i32 * depth as i32).min(400)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{history_bonus, History, MAX_HISTORY};
+    use crate::game::board::{Board, Move, STARTPOS};
+    use crate::game::color::Color;
+
+    #[test]
+    fn gravity() {
+        let board = Board::new(STARTPOS);
+        let m = Move::from_uci("g1f3", &board).unwrap();
+        let mut history = History::new();
+
+        // from 0 a bonus is added in full, and only to the side that moved
+        history.update(Color::White, &m, 400);
+        assert_eq!(history.get(Color::White, &m), 400);
+        assert_eq!(history.get(Color::Black, &m), 0);
+        // taking it away again also takes off the share gravity pulls towards 0
+        history.update(Color::White, &m, -400);
+        assert_eq!(history.get(Color::White, &m), -9);
+
+        // the closer to the limit the less it moves, and it never goes past it
+        let mut last = history.get(Color::White, &m);
+        let mut step = i32::MAX;
+        for _ in 0..1000 {
+            history.update(Color::White, &m, history_bonus(20));
+            let score = history.get(Color::White, &m);
+            assert!(score <= MAX_HISTORY);
+            assert!(score - last <= step);
+            step = score - last;
+            last = score;
+        }
+        assert!(last > MAX_HISTORY * 9 / 10);
+
+        // bonuses past the limit are clamped to it
+        history.update(Color::White, &m, i32::MIN + 1);
+        assert!(history.get(Color::White, &m).abs() <= MAX_HISTORY);
+        for _ in 0..1000 {
+            history.update(Color::White, &m, -10 * MAX_HISTORY);
+            assert!(history.get(Color::White, &m) >= -MAX_HISTORY);
+        }
+        assert_eq!(history.get(Color::White, &m), -MAX_HISTORY);
+    }
+
+    #[test]
+    fn bonus() {
+        assert_eq!(history_bonus(1), 1);
+        assert_eq!(history_bonus(5), 25);
+        assert_eq!(history_bonus(20), 400);
+        assert_eq!(history_bonus(40), 400);
+    }
+}
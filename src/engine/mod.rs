@@ -1,4 +1,11 @@
-pub mod search;
+//! Evaluation and search.
+
+pub mod bench;
 pub mod eval;
+pub mod hash;
+pub mod history;
+pub mod movepick;
 pub mod piecemaps;
-pub mod hash;
\ No newline at end of file
+pub mod search;
+pub mod see;
+pub mod transposition;
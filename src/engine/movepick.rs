@@ -0,0 +1,269 @@
+//! Staged move ordering for the search.
+
+use crate::game::{
+    board::{Board, Move, PieceTypes, QUEEN_PROMOTION},
+    movegen::{generate_captures, generate_evasions, generate_quiets, in_check},
+    movelist::MoveList,
+};
+
+use super::{history::History, search::get_value};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stage {
+    TTMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    Countermove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    GenerateEvasions,
+    Evasions,
+    Done,
+}
+
+/// hands out pseudo-legal moves one at a time, best guesses first, and only
+/// generates the moves of a stage once the search actually gets there
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: Move,
+    killers: [Move; 2],
+    countermove: Move,
+    moves: MoveList,
+    bad_captures: MoveList,
+    index: usize,
+    killer_index: usize,
+}
+
+impl MovePicker {
+    /// a picker for the moves of `board`, trying the tt move, killers and countermove
+    /// before the other quiet moves. a tt move that cannot be played here is left out
+    pub fn new(board: &Board, tt_move: Move, killers: [Move; 2], countermove: Move) -> MovePicker {
+        let tt_move = if board.is_pseudo_legal(&tt_move) {
+            tt_move
+        } else {
+            Move::NULL
+        };
+
+        MovePicker {
+            stage: if in_check(board) {
+                Stage::GenerateEvasions
+            } else {
+                Stage::TTMove
+            },
+            tt_move,
+            killers,
+            countermove,
+            moves: MoveList::new(),
+            bad_captures: MoveList::new(),
+            index: 0,
+            killer_index: 0,
+        }
+    }
+
+    /// quiet moves are ordered by their `history` scores
+    pub fn next(&mut self, board: &Board, history: &History) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TTMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if !self.tt_move.is_null() {
+                        return Some(self.tt_move);
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    generate_captures(board, &mut self.moves);
+                    score_captures(board, &mut self.moves);
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => {
+                    if self.index == self.moves.len() {
+                        self.stage = Stage::Killers;
+                        continue;
+                    }
+                    let m = self.moves.pick_best(self.index);
+                    self.index += 1;
+                    if m == self.tt_move {
+                        continue;
+                    }
+                    if !board.see_ge(&m, 0) {
+                        self.bad_captures.push(m);
+                        continue;
+                    }
+                    return Some(m);
+                }
+                Stage::Killers => {
+                    if self.killer_index == self.killers.len() {
+                        self.stage = Stage::Countermove;
+                        continue;
+                    }
+                    let killer = self.killers[self.killer_index];
+                    self.killer_index += 1;
+                    if self.is_new_quiet(board, &killer) {
+                        return Some(killer);
+                    }
+                }
+                Stage::Countermove => {
+                    self.stage = Stage::GenerateQuiets;
+                    let countermove = self.countermove;
+                    if self.is_new_quiet(board, &countermove) && !self.killers.contains(&countermove)
+                    {
+                        return Some(countermove);
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.moves.clear();
+                    self.index = 0;
+                    generate_quiets(board, &mut self.moves);
+                    for i in 0..self.moves.len() {
+                        let score = history.get(board.side_to_move, &self.moves[i]);
+                        self.moves.set_score(i, score);
+                    }
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    if self.index == self.moves.len() {
+                        self.index = 0;
+                        self.stage = Stage::BadCaptures;
+                        continue;
+                    }
+                    let m = self.moves.pick_best(self.index);
+                    self.index += 1;
+                    if m == self.tt_move || self.killers.contains(&m) || m == self.countermove {
+                        continue;
+                    }
+                    return Some(m);
+                }
+                Stage::BadCaptures => {
+                    if self.index == self.bad_captures.len() {
+                        self.stage = Stage::Done;
+                        continue;
+                    }
+                    self.index += 1;
+                    return Some(self.bad_captures[self.index - 1]);
+                }
+                Stage::GenerateEvasions => {
+                    generate_evasions(board, &mut self.moves);
+                    score_captures(board, &mut self.moves);
+                    for i in 0..self.moves.len() {
+                        if self.moves[i] == self.tt_move {
+                            self.moves.set_score(i, i32::MAX);
+                        }
+                    }
+                    self.stage = Stage::Evasions;
+                }
+                Stage::Evasions => {
+                    if self.index == self.moves.len() {
+                        self.stage = Stage::Done;
+                        continue;
+                    }
+                    let m = self.moves.pick_best(self.index);
+                    self.index += 1;
+                    return Some(m);
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    // killers and countermoves come from other positions, so they have to be
+    // checked against this board before they are played
+    fn is_new_quiet(&self, board: &Board, m: &Move) -> bool {
+        *m != self.tt_move
+            && !m.is_capture()
+            && m.flags() != QUEEN_PROMOTION
+            && board.is_pseudo_legal(m)
+    }
+}
+
+// MVV-LVA, captures ahead of quiet moves
+fn score_captures(board: &Board, moves: &mut MoveList) {
+    for i in 0..moves.len() {
+        let m = moves[i];
+        let mut score = get_value(m.promotion()) as i32 * 10;
+        if m.is_capture() {
+            score += 1000 + victim_value(board, &m) as i32 * 10
+                - get_value(board.board[m.from().index()].piece_type) as i32;
+        }
+        moves.set_score(i, score);
+    }
+}
+
+fn victim_value(board: &Board, m: &Move) -> i16 {
+    if m.is_ep() {
+        get_value(PieceTypes::Pawn)
+    } else {
+        get_value(board.board[m.to().index()].piece_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MovePicker;
+    use crate::engine::history::History;
+    use crate::game::board::{Board, Move};
+    use crate::game::movegen::generate_legal_moves;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    // every legal move comes out exactly once whatever the picker is seeded with, and the tt
+    // move comes first when it can be played
+    fn assert_picks(board: &mut Board, foreign: Move, depth: u8) {
+        let legal = generate_legal_moves(board);
+        let quiet = legal.iter().rev().find(|m| !m.is_capture()).copied().unwrap_or(Move::NULL);
+        let seeds = [
+            (Move::NULL, [Move::NULL; 2], Move::NULL),
+            (legal.iter().next().copied().unwrap_or(Move::NULL), [quiet, foreign], quiet),
+            (foreign, [foreign, quiet], foreign),
+            (quiet, [quiet, quiet], legal.iter().last().copied().unwrap_or(Move::NULL)),
+        ];
+
+        let history = History::new();
+        for (tt_move, killers, countermove) in seeds {
+            let mut picker = MovePicker::new(board, tt_move, killers, countermove);
+            let mut picked = Vec::new();
+            while let Some(m) = picker.next(board, &history) {
+                picked.push(m);
+            }
+
+            if board.is_legal(&tt_move) {
+                assert_eq!(picked.first(), Some(&tt_move), "{}", board.fen());
+            }
+            let mut sorted = picked.clone();
+            sorted.sort_by_key(|m| m.uci());
+            sorted.dedup();
+            assert_eq!(sorted.len(), picked.len(), "{}", board.fen());
+
+            picked.retain(|m| board.is_legal(m));
+            let mut expected: Vec<Move> = legal.iter().copied().collect();
+            picked.sort_by_key(|m| m.uci());
+            expected.sort_by_key(|m| m.uci());
+            assert_eq!(picked, expected, "{}", board.fen());
+        }
+
+        if depth == 0 {
+            return;
+        }
+        for m in legal {
+            let undo = board.push(&m);
+            assert_picks(board, foreign, depth - 1);
+            undo(board);
+        }
+    }
+
+    #[test]
+    fn every_move_once() {
+        let kiwipete = Board::new(KIWIPETE);
+        // moves from another position stand in for stale tt entries and killers
+        for (fen, foreign, depth) in [
+            (KIWIPETE, "e2a6", 1),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", "e5g4", 2),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", "e1g1", 2),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", "d5d6", 1),
+        ] {
+            let foreign = Move::from_uci(foreign, &kiwipete).unwrap();
+            assert_picks(&mut Board::new(fen), foreign, depth);
+        }
+    }
+}
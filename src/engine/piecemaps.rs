@@ -1,4 +1,7 @@
-pub const WHITE_PAWN_MAP: [f32; 64] = [
+//! Piece-square tables used by the evaluation.
+
+/// drawn from white's side of the board, black looks its squares up flipped
+pub const PAWN_MAP: [f32; 64] = [
     1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
     3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0,
     2.0, 2.0, 2.5, 2.5, 2.5, 2.0, 2.0, 2.0,
@@ -9,17 +12,7 @@ pub const WHITE_PAWN_MAP: [f32; 64] = [
     1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
 ];
 
-pub const BLACK_PAWN_MAP: [f32; 64] = [
-    1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
-    1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
-    1.0, 1.0, 1.25, 1.25, 1.25, 0.9, 0.9, 1.0,
-    1.0, 1.0, 1.5, 1.5, 1.5, 0.7, 0.7, 1.0,
-    1.5, 1.5, 2.0, 2.0, 2.0, 1.5, 1.5, 1.5,
-    2.0, 2.0, 2.5, 2.5, 2.5, 2.0, 2.0, 2.0,
-    3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0,
-    1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
-];
-
+/// used for bishops for now, higher towards the centre
 pub const KNIGHT_MAP: [f32; 64] = [
     1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
     1.0, 1.1, 1.1, 1.1, 1.1, 1.1, 1.1, 1.0,
@@ -29,4 +22,4 @@ pub const KNIGHT_MAP: [f32; 64] = [
     1.0, 1.1, 1.2, 1.2, 1.2, 1.2, 1.1, 1.0,
     1.0, 1.1, 1.1, 1.1, 1.1, 1.1, 1.1, 1.0,
     1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
-];
\ No newline at end of file
+];
@@ -1,19 +1,292 @@
+//! Alpha-beta search and quiescence search.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
 use crate::game::{
-    board::{Board, Move, PieceTypes, TranspositionEntry},
-    movegen::{generate_legal_captures, generate_legal_moves, is_check},
+    board::{Board, Move, PieceTypes},
+    color::Color,
+    movegen::{
+        generate_legal_captures, generate_legal_moves, generate_quiet_checks, in_check, is_check,
+    },
+    movelist::MoveList,
 };
 
-use super::eval::evaluate;
+use super::{
+    eval::{evaluate, Score},
+    history::{history_bonus, History},
+    movepick::MovePicker,
+    transposition::{Bound, TranspositionEntry, TranspositionTable},
+};
+
+/// the score of being checkmated at the root, a mate n plies away scores n more
+pub const REALLY_SMALL_NUMBER: Score = -100_000;
+/// the score of checkmating at the root, a mate n plies away scores n less
+pub const REALLY_BIG_NUMBER: Score = 100_000;
+
+/// how far either side of the previous score the first window of an iteration reaches
+pub const ASPIRATION_WINDOW: Score = 50;
+
+/// depth_from_root is a u8, so no search gets deeper than this
+pub const MAX_PLY: usize = 256;
+/// scores beyond this are mates found by the search rather than evaluations
+pub const MATE_BOUND: Score = REALLY_BIG_NUMBER - MAX_PLY as Score;
+
+/// stack size of every search thread, the main one included. the search recurses once per
+/// ply with its move lists on the stack, which can outgrow the 2 MiB of a default thread
+pub const SEARCH_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// null moves are tried from this depth on
+pub const NULL_MOVE_DEPTH: u8 = 3;
+/// from this depth on a null move cutoff is only trusted once a normal search confirms it
+pub const NULL_MOVE_VERIFICATION_DEPTH: u8 = 10;
+
+/// the tt move is tested for being singular from this depth on
+pub const SINGULAR_DEPTH: u8 = 6;
+/// how far below the tt move's score, per ply of depth, the other moves have to stay
+pub const SINGULAR_MARGIN: Score = 2;
+
+/// late moves are reduced from this depth on
+pub const LMR_DEPTH: u8 = 3;
+/// how many moves of a node are searched to full depth before the rest are reduced
+pub const LMR_FULL_DEPTH_MOVES: usize = 3;
 
-pub const REALLY_SMALL_NUMBER: f32 = -1000.0;
-pub const REALLY_BIG_NUMBER: f32 = 1000.0;
+/// reverse futility pruning is tried up to this depth
+pub const REVERSE_FUTILITY_DEPTH: u8 = 6;
+/// razoring is tried up to this depth
+pub const RAZORING_DEPTH: u8 = 3;
+/// quiet moves are futility pruned up to this depth
+pub const FUTILITY_DEPTH: u8 = 3;
 
+/// what a capture may gain in the quiescence search beyond the piece it takes, for the
+/// positional changes that come with it
+pub const DELTA_MARGIN: Score = 200;
+
+/// what the last completed root search found
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct EvalResult {
+    /// the first move of the best line
     pub best_move: Move,
+    /// from the side to move's point of view, in centipawns or
+    /// REALLY_BIG_NUMBER minus the plies to a mate
+    pub score: Score,
+    /// starts with best_move
+    pub pv: Vec<Move>,
+    /// the deepest iteration that completed
+    pub depth: u8,
+    /// positions searched, counted across iterations and, in the final result, threads
+    pub nodes: u64,
+    /// the best `multi_pv` moves with their own scores and pvs, best first. the first one
+    /// is the same as best_move, score and pv
+    pub lines: Vec<PvLine>,
+}
+
+/// one of the moves at the root and the line that follows it
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PvLine {
+    /// from the side to move's point of view, as EvalResult::score
+    pub score: Score,
+    /// starts with the move
+    pub pv: Vec<Move>,
+}
+
+/// reads an EvalResult back against the board the search started from. every pv is
+/// played out from there, so each move is parsed in the position it is played in
+#[cfg(feature = "serde")]
+#[derive(Clone, Copy)]
+pub struct EvalResultSeed<'a>(pub &'a Board);
+
+// the fields as they are written, before the moves are parsed
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct EvalResultFields {
+    best_move: String,
+    score: Score,
+    pv: Vec<String>,
+    depth: u8,
+    nodes: u64,
+    lines: Vec<PvLineFields>,
 }
 
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct PvLineFields {
+    score: Score,
+    pv: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::DeserializeSeed<'de> for EvalResultSeed<'_> {
+    type Value = EvalResult;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<EvalResult, D::Error> {
+        use serde::de::Error;
+
+        let fields = <EvalResultFields as serde::Deserialize>::deserialize(deserializer)?;
+        let play = |pv: &[String]| -> Result<Vec<Move>, D::Error> {
+            let mut board = self.0.clone();
+            pv.iter()
+                .map(|uci| {
+                    let m = Move::from_uci(uci, &board).map_err(D::Error::custom)?;
+                    let _ = board.try_push(&m).map_err(D::Error::custom)?;
+                    Ok(m)
+                })
+                .collect()
+        };
+        let lines = fields
+            .lines
+            .iter()
+            .map(|line| {
+                Ok(PvLine {
+                    score: line.score,
+                    pv: play(&line.pv)?,
+                })
+            })
+            .collect::<Result<_, D::Error>>()?;
+        Ok(EvalResult {
+            best_move: Move::from_uci(&fields.best_move, self.0).map_err(D::Error::custom)?,
+            score: fields.score,
+            pv: play(&fields.pv)?,
+            depth: fields.depth,
+            nodes: fields.nodes,
+            lines,
+        })
+    }
+}
+
+/// switches for the parts of the search that trade accuracy for speed, so they can be
+/// compared against each other
+#[derive(Clone, Copy, Debug)]
+pub struct SearchParams {
+    /// prune when passing the move still fails high (null move pruning)
+    pub null_move: bool,
+    /// confirm null move cutoffs from NULL_MOVE_VERIFICATION_DEPTH on
+    pub null_move_verification: bool,
+    /// search quiet moves late in the list less deep
+    pub late_move_reductions: bool,
+    /// search checks, and tt moves that are singular, one ply deeper
+    pub extensions: bool,
+    /// order quiet moves by killers, countermoves and history rather than as generated
+    pub quiet_move_ordering: bool,
+    /// return beta when the static evaluation is far above it
+    pub reverse_futility_pruning: bool,
+    /// how far above beta, per ply of depth, the static evaluation has to be
+    pub reverse_futility_margin: Score,
+    /// drop into the quiescence search when the static evaluation is far below alpha
+    pub razoring: bool,
+    /// how far below alpha, per ply of depth, the static evaluation has to be
+    pub razoring_margin: Score,
+    /// skip quiet moves at the frontier when the static evaluation is far below alpha
+    pub futility_pruning: bool,
+    /// how much, per ply of depth, a quiet move is assumed to be able to gain
+    pub futility_margin: Score,
+    /// the main search thread and threads - 1 helpers
+    pub threads: usize,
+    /// how many of the best root moves to find, each with its own score and pv
+    pub multi_pv: usize,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            null_move: true,
+            null_move_verification: true,
+            late_move_reductions: true,
+            extensions: true,
+            quiet_move_ordering: true,
+            reverse_futility_pruning: true,
+            reverse_futility_margin: 80,
+            razoring: true,
+            razoring_margin: 200,
+            futility_pruning: true,
+            futility_margin: 100,
+            threads: 1,
+            multi_pv: 1,
+        }
+    }
+}
+
+/// everything a search carries from one node to the next
+pub struct SearchState<'a> {
+    /// the switches the search was started with
+    pub params: SearchParams,
+    /// shared with the other threads of the search
+    pub tt: &'a TranspositionTable,
+    /// set to make the search return as soon as it can, with a result that means nothing
+    pub stop: &'a AtomicBool,
+    /// what the search has found so far, and its node count
+    pub result: EvalResult,
+    /// the move that was played at each ply of the current line, Move::NULL for a null move
+    pub moves: [Move; MAX_PLY],
+    /// turned off while a null move cutoff is being verified
+    pub null_move_allowed: bool,
+    /// how many plies to take off a late move, by depth and by how many moves came before it
+    pub reductions: [[u8; 64]; 64],
+    /// the last two quiet moves that caused a cutoff at each ply
+    pub killers: [[Move; 2]; MAX_PLY],
+    /// the quiet move that last refuted a move, indexed by its from and to squares
+    pub countermoves: [[Move; 64]; 64],
+    /// how often each quiet move caused a cutoff
+    pub history: History,
+    /// a move to leave out at each ply, set while testing whether the tt move is singular
+    pub excluded: [Move; MAX_PLY],
+    /// the depth of the current iteration, which extensions may at most double
+    pub root_depth: u8,
+    /// the first moves of the multipv lines already found in this iteration, which the
+    /// root search for the next line leaves out
+    pub root_excluded: Vec<Move>,
+}
+
+impl<'a> SearchState<'a> {
+    /// a fresh state for one thread of a search
+    pub fn new(
+        params: SearchParams,
+        tt: &'a TranspositionTable,
+        stop: &'a AtomicBool,
+    ) -> SearchState<'a> {
+        SearchState {
+            params,
+            tt,
+            stop,
+            result: EvalResult::default(),
+            moves: [Move::NULL; MAX_PLY],
+            null_move_allowed: true,
+            reductions: reduction_table(),
+            killers: [[Move::NULL; 2]; MAX_PLY],
+            countermoves: [[Move::NULL; 64]; 64],
+            history: History::new(),
+            excluded: [Move::NULL; MAX_PLY],
+            root_depth: 0,
+            root_excluded: Vec::new(),
+        }
+    }
+
+    /// whether the search was told to stop
+    pub fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+// grows with both the depth and the move number, but slowly: a move ordered late is
+// unlikely to be best, not certain to be
+fn reduction_table() -> [[u8; 64]; 64] {
+    let mut table = [[0; 64]; 64];
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (move_number, reduction) in row.iter_mut().enumerate().skip(1) {
+            *reduction = (0.75 + (depth as f64).ln() * (move_number as f64).ln() / 2.25) as u8;
+        }
+    }
+    table
+}
+
+/// material value in pawns, used to order captures
 pub fn get_value(piece: PieceTypes) -> i16 {
-    return match piece {
+    match piece {
         PieceTypes::Pawn => 1,
         PieceTypes::Bishop => 3,
         PieceTypes::Knight => 3,
@@ -21,143 +294,1348 @@ pub fn get_value(piece: PieceTypes) -> i16 {
         PieceTypes::Queen => 9,
         PieceTypes::King => 100,
         _ => 0,
-    };
+    }
+}
+
+/// sorts the moves by what they win in the exchange they start, with the tt move first
+pub fn order_moves(moves: &mut MoveList, board: &Board, tt_move: Move) {
+    for i in 0..moves.len() {
+        let score = if moves[i] == tt_move {
+            REALLY_BIG_NUMBER
+        } else {
+            board.see(&moves[i])
+        };
+        moves.set_score(i, score);
+    }
+    moves.sort_by_score();
+}
+
+/// searches one ply deeper at a time up to `depth`, each iteration with an aspiration
+/// window around the score of the one before. `report` is called after every iteration
+/// of the main thread. with more than one thread the helpers search the same position
+/// with their own copy of the board (lazy smp): they share nothing but `tt`, and what
+/// they store there lets the main thread cut off earlier
+pub fn iterative_deepening(
+    board: &mut Board,
+    depth: u8,
+    params: SearchParams,
+    tt: &TranspositionTable,
+    mut report: impl FnMut(&EvalResult) + Send,
+) -> EvalResult {
+    let stop = AtomicBool::new(false);
+    tt.new_search();
+    thread::scope(|scope| {
+        let helpers: Vec<_> = (1..params.threads)
+            .map(|id| {
+                let mut board = board.clone();
+                let stop = &stop;
+                search_thread(scope, move || {
+                    // every other helper is a ply ahead, so they do not all search the
+                    // same tree in the same order
+                    let offset = (id % 2) as u8;
+                    let mut state = SearchState::new(params, tt, stop);
+                    for current_depth in 1..=depth {
+                        let completed = state.result.clone();
+                        search_lines(current_depth.saturating_add(offset), &mut board, &mut state);
+                        if state.stopped() {
+                            state.result = EvalResult {
+                                nodes: state.result.nodes,
+                                ..completed
+                            };
+                            break;
+                        }
+                    }
+                    state.result
+                })
+            })
+            .collect();
+
+        let stop = &stop;
+        let (mut result, mut report) = search_thread(scope, move || {
+            let mut state = SearchState::new(params, tt, stop);
+            for current_depth in 1..=depth {
+                search_lines(current_depth, board, &mut state);
+                report(&state.result);
+            }
+            stop.store(true, Ordering::Relaxed);
+            (state.result, report)
+        })
+        .join()
+        .unwrap();
+
+        // a helper that completed a deeper iteration than the main thread has the better move
+        let mut deeper = false;
+        for helper in helpers {
+            let helper_result = helper.join().unwrap();
+            result.nodes += helper_result.nodes;
+            if helper_result.depth > result.depth {
+                result = EvalResult {
+                    nodes: result.nodes,
+                    ..helper_result
+                };
+                deeper = true;
+            }
+        }
+        if deeper {
+            report(&result);
+        }
+        result
+    })
+}
+
+// spawns a thread of the search with a stack deep enough for it
+fn search_thread<'scope, T: Send + 'scope>(
+    scope: &'scope thread::Scope<'scope, '_>,
+    f: impl FnOnce() -> T + Send + 'scope,
+) -> thread::ScopedJoinHandle<'scope, T> {
+    thread::Builder::new()
+        .stack_size(SEARCH_STACK_SIZE)
+        .spawn_scoped(scope, f)
+        .unwrap()
 }
 
-pub fn order_moves(moves: &mut Vec<Move>, board: &Board) {
-    fn guess_move(m: &Move, board: &Board) -> i16 {
-        if board.board[m.to as usize].piece_type != PieceTypes::Empty {
-            return get_value(board.board[m.to as usize].piece_type)
-                - get_value(board.board[m.from as usize].piece_type);
+// one root search per multipv line, each leaving out the moves the lines before it start
+// with. the lines are sorted afterwards, since a later search can find a better score
+// than an earlier one that was cut off sooner
+fn search_lines(depth: u8, board: &mut Board, state: &mut SearchState) {
+    let count = state.params.multi_pv.min(generate_legal_moves(board).len()).max(1);
+    let previous = std::mem::take(&mut state.result.lines);
+    let mut lines = Vec::with_capacity(count);
+    for i in 0..count {
+        // each line's window is centred on its own score from the last iteration
+        if let Some(line) = previous.get(i) {
+            state.result.score = line.score;
+        }
+        aspiration_search(depth, board, state);
+        if state.stopped() {
+            break;
         }
-        0
+        lines.push(PvLine {
+            score: state.result.score,
+            pv: state.result.pv.clone(),
+        });
+        state.root_excluded.push(state.result.best_move);
     }
-    moves.sort_by_key(|x| guess_move(x, board));
-    moves.reverse();
+    state.root_excluded.clear();
+
+    lines.sort_by_key(|line| -line.score);
+    if let Some(best) = lines.first() {
+        state.result.score = best.score;
+        state.result.pv = best.pv.clone();
+        state.result.best_move = best.pv.first().copied().unwrap_or(state.result.best_move);
+    }
+    state.result.lines = lines;
 }
 
+// the previous iteration's score is usually close, so a narrow window around it cuts
+// off more. a score outside the window only says which side it is on, so the search
+// is repeated with that side of the window twice as wide until the score fits
+fn aspiration_search(depth: u8, board: &mut Board, state: &mut SearchState) -> Score {
+    let (min, max) = (REALLY_SMALL_NUMBER * 10, REALLY_BIG_NUMBER * 10);
+    let mut delta = ASPIRATION_WINDOW;
+    let (mut alpha, mut beta) = if depth > 1 {
+        (state.result.score - delta, state.result.score + delta)
+    } else {
+        (min, max)
+    };
+
+    loop {
+        let score = absearch(depth, board, alpha, beta, state, 0);
+        if state.stopped() {
+            return score;
+        }
+        if score <= alpha && alpha > min {
+            alpha = (alpha - delta).max(min);
+        } else if score >= beta && beta < max {
+            beta = (beta + delta).min(max);
+        } else {
+            return score;
+        }
+        delta *= 2;
+    }
+}
+
+/// alpha-beta search to `depth` plies followed by a quiescence search, returning the
+/// score from the side to move's point of view. at the root (`depth_from_root` 0)
+/// the best move, score, pv and depth are stored in `state.result`
 pub fn absearch(
     depth: u8,
     board: &mut Board,
-    mut alpha: f32,
-    beta: f32,
-    mut eval_result: &mut EvalResult,
+    mut alpha: Score,
+    beta: Score,
+    state: &mut SearchState,
     depth_from_root: u8,
-    line: &mut Vec<Move>
-) -> f32 {
-    println!("{:?}, {}", line, depth_from_root);
-    if depth_from_root > 0 {
-        let entry = board.transposition_table.get(&board.hash);
-        match entry {
-            Some(&entry_unwrap) => {
-                if entry_unwrap.depth >= depth {
-                    println!("t found: {:?} with hash {:#066b}, depth {}, eval: {}", line, board.hash, depth_from_root, entry_unwrap.eval);
-                    return entry_unwrap.eval;
-                }
+) -> Score {
+    state.result.nodes += 1;
+    if state.stopped() {
+        return 0;
+    }
+    if depth_from_root == 0 {
+        state.root_depth = depth;
+    }
+
+    // the search without the excluded move is a different one from the search of this
+    // position, so it neither uses nor overwrites its table entry
+    let ply = depth_from_root as usize;
+    let excluded = state.excluded[ply];
+    // the same goes for a root search that leaves out the moves of earlier multipv lines
+    let excluding_root = ply == 0 && !state.root_excluded.is_empty();
+    let tt_entry = if excluded.is_null() {
+        probe(state.tt, board.hash, depth_from_root)
+    } else {
+        None
+    };
+
+    let mut tt_move = Move::NULL;
+    if let Some(entry) = tt_entry {
+        if depth_from_root > 0 && entry.depth >= depth {
+            match entry.bound {
+                Bound::Exact => return entry.eval.clamp(alpha, beta),
+                Bound::Lower if entry.eval >= beta => return beta,
+                Bound::Upper if entry.eval <= alpha => return alpha,
+                _ => (),
             }
-            None => (),
         }
+        tt_move = entry.best_move;
     }
     if depth == 0 {
-        let eval = search_captures(board, alpha, beta);
-        println!("final position: {:?}: {}", line, eval);
-        return eval;
-        // return evaluate(board);
+        return search_captures(board, alpha, beta, state, true, depth_from_root);
     }
 
-    let mut legal_moves = generate_legal_moves(board);
-    order_moves(&mut legal_moves, board);
+    let checked = in_check(board);
+    let can_prune = can_prune(checked, alpha, beta, depth_from_root, excluded);
+    let static_eval = if can_prune { evaluate(board) } else { 0 };
 
-    if legal_moves.len() == 0 {
-        if is_check(
-            board,
-            if board.wtomove {
-                board.wkingpos
-            } else {
-                board.bkingpos
-            },
-            board.wtomove,
-        ) {
-            println!("final position: {:?}: mate", line);
-            return REALLY_SMALL_NUMBER + depth_from_root as f32;
+    if can_prune {
+        if let Some(score) = reverse_futility(depth, beta, state, static_eval) {
+            return score;
+        }
+        if let Some(score) = razor(depth, board, alpha, state, static_eval, depth_from_root) {
+            return score;
         }
-        return 0.0;
     }
 
-    for m in &legal_moves {
+    if excluded.is_null() {
+        if let Some(score) = null_move_search(depth, board, alpha, beta, state, depth_from_root) {
+            return score;
+        }
+    }
+
+    // extensions stop once a line is twice as long as the iteration's depth, so a long
+    // series of checks cannot keep the search going forever
+    let can_extend = state.params.extensions
+        && ply < 2 * state.root_depth as usize
+        && ply + (depth as usize) < MAX_PLY - 2;
+    let singular = can_extend
+        && tt_entry.is_some_and(|entry| is_singular(depth, board, entry, state, depth_from_root));
+
+    let previous = if ply > 0 {
+        state.moves[ply - 1]
+    } else {
+        Move::NULL
+    };
+    let countermove = if previous.is_null() {
+        Move::NULL
+    } else {
+        state.countermoves[previous.from().index()][previous.to().index()]
+    };
+
+    let futile = can_prune && futile(depth, alpha, state, static_eval);
+
+    let mut picker = MovePicker::new(board, tt_move, state.killers[ply], countermove);
+    let mut legal_moves = 0;
+    let mut best_move = Move::NULL;
+    // the quiet moves that did not cause a cutoff, they lose history if a later one does
+    let mut quiets_tried = MoveList::new();
+
+    while let Some(m) = picker.next(board, &state.history) {
+        if m == excluded || (ply == 0 && state.root_excluded.contains(&m)) {
+            continue;
+        }
         let undo = board.push(&m);
-        line.push(*m);
-        let eval = -absearch(
-            depth - 1,
-            board,
-            -beta,
-            -alpha,
-            &mut eval_result,
-            depth_from_root + 1,
-            line
-        );
-        line.pop();
-    
+        if leaves_king_in_check(board) {
+            undo(board);
+            continue;
+        }
+        legal_moves += 1;
+        state.moves[ply] = m;
+
+        // checks are forcing, so the line is followed one ply further to see where it leads
+        let gives_check = in_check(board);
+        if futile && legal_moves > 1 && !m.is_capture() && !m.is_promotion() && !gives_check {
+            undo(board);
+            continue;
+        }
+        let extension = u8::from(can_extend && (gives_check || (singular && m == tt_move)));
+        let new_depth = depth - 1 + extension;
+
+        let reduction = late_move_reduction(state, &m, depth, legal_moves, checked, gives_check)
+            .min(new_depth.saturating_sub(1));
+
+        // principal variation search: once a move has been searched with the full window
+        // the others only have to be shown to be worse, which a null window around alpha
+        // does cheaply. the few that turn out better are searched again to get their score
+        let null_window = |board: &mut Board, state: &mut SearchState, depth: u8| {
+            -absearch(depth, board, -alpha - 1, -alpha, state, depth_from_root + 1)
+        };
+        let full_window = |board: &mut Board, state: &mut SearchState| {
+            -absearch(new_depth, board, -beta, -alpha, state, depth_from_root + 1)
+        };
+        let eval = if legal_moves == 1 {
+            full_window(board, state)
+        } else {
+            let mut eval = null_window(board, state, new_depth - reduction);
+            if reduction > 0 && eval > alpha {
+                eval = null_window(board, state, new_depth);
+            }
+            if eval > alpha && eval < beta {
+                full_window(board, state)
+            } else {
+                eval
+            }
+        };
+
         undo(board);
+        // what a stopped search returns means nothing, so it is neither stored nor used
+        if state.stopped() {
+            return 0;
+        }
 
+        let quiet = !m.is_capture() && !m.is_promotion();
         if eval >= beta {
-            println!("saving eval {} with line {:?} in position {:#066b}", beta, line, board.hash);
-            board.transposition_table.insert(
-                board.hash,
-                TranspositionEntry {
-                    depth: depth,
+            if quiet && state.params.quiet_move_ordering {
+                update_quiet_heuristics(board, state, m, &quiets_tried, depth, ply, previous);
+            }
+            if excluded.is_null() && !excluding_root {
+                let entry = TranspositionEntry {
+                    depth,
                     eval: beta,
-                },
-            );
+                    bound: Bound::Lower,
+                    best_move: m,
+                };
+                store(state.tt, board.hash, entry, depth_from_root);
+            }
+            if depth_from_root == 0 {
+                state.result.best_move = m;
+            }
             return beta;
         }
 
         if eval > alpha {
-            let prev_eval = eval;
             alpha = eval;
-            println!("alpha: {}, eval: {}, depth_from_root: {}", alpha, prev_eval, depth_from_root);
+            best_move = m;
             if depth_from_root == 0 {
-                println!("alpha: {}, eval: {}", alpha, prev_eval);
-                eval_result.best_move = m.clone();
+                state.result.best_move = m;
             }
         }
+        if quiet {
+            quiets_tried.push(m);
+        }
     }
 
-    println!("saving eval {} with line {:?} in position {:#066b}", alpha, line, board.hash);
-    board.transposition_table.insert(
-        board.hash,
-        TranspositionEntry {
-            depth: depth,
+    if !excluded.is_null() {
+        // without its best move the position is not lost, it just has nothing better than alpha
+        return alpha;
+    }
+    if legal_moves == 0 {
+        alpha = if in_check(board) {
+            REALLY_SMALL_NUMBER + depth_from_root as Score
+        } else {
+            0
+        };
+    } else if !excluding_root {
+        // when no move reached alpha, the one the table had is still the best guess
+        let entry = TranspositionEntry {
+            depth,
             eval: alpha,
-        },
-    );
+            bound: if best_move.is_null() {
+                Bound::Upper
+            } else {
+                Bound::Exact
+            },
+            best_move: if best_move.is_null() {
+                tt_move
+            } else {
+                best_move
+            },
+        };
+        store(state.tt, board.hash, entry, depth_from_root);
+    }
+
+    if depth_from_root == 0 {
+        state.result.score = alpha;
+        state.result.depth = depth;
+        state.result.pv = principal_variation(board, state.tt, state.result.best_move, depth);
+    }
 
     alpha
 }
 
-pub fn search_captures(board: &mut Board, mut alpha: f32, mut beta: f32) -> f32 {
-    let mut legal_captures = generate_legal_captures(board);
-    order_moves(&mut legal_captures, board);
+// late move reductions: with good move ordering the moves near the end of the list rarely
+// matter, so quiet ones are searched less deep unless they turn out to beat alpha. the
+// first few moves are not reduced, and neither are captures, promotions, checks or moves
+// out of check
+fn late_move_reduction(
+    state: &SearchState,
+    m: &Move,
+    depth: u8,
+    move_number: usize,
+    checked: bool,
+    gives_check: bool,
+) -> u8 {
+    if !state.params.late_move_reductions
+        || depth < LMR_DEPTH
+        || move_number <= LMR_FULL_DEPTH_MOVES
+        || checked
+        || m.is_capture()
+        || m.is_promotion()
+        || gives_check
+    {
+        return 0;
+    }
+    state.reductions[(depth as usize).min(63)][move_number.min(63)]
+}
+
+// the tt move is singular when a reduced search of all the other moves stays clearly below
+// its score. a position with only one good move is one where a mistake costs the most, so
+// that move gets searched deeper
+fn is_singular(
+    depth: u8,
+    board: &mut Board,
+    entry: TranspositionEntry,
+    state: &mut SearchState,
+    depth_from_root: u8,
+) -> bool {
+    if depth_from_root == 0
+        || depth < SINGULAR_DEPTH
+        || entry.best_move.is_null()
+        || entry.bound == Bound::Upper
+        || entry.depth + 3 < depth
+        || entry.eval.abs() >= MATE_BOUND
+    {
+        return false;
+    }
+
+    let singular_beta = entry.eval - SINGULAR_MARGIN * depth as Score;
+    let ply = depth_from_root as usize;
+    state.excluded[ply] = entry.best_move;
+    let eval = absearch(depth / 2, board, singular_beta - 1, singular_beta, state, depth_from_root);
+    state.excluded[ply] = Move::NULL;
+    eval < singular_beta
+}
+
+// a quiet move that refutes one position often refutes its siblings too: it becomes a
+// killer for this ply, the countermove to the move before it, and gains history
+fn update_quiet_heuristics(
+    board: &Board,
+    state: &mut SearchState,
+    m: Move,
+    quiets_tried: &MoveList,
+    depth: u8,
+    ply: usize,
+    previous: Move,
+) {
+    let killers = &mut state.killers[ply];
+    if killers[0] != m {
+        killers[1] = killers[0];
+        killers[0] = m;
+    }
+    if !previous.is_null() {
+        state.countermoves[previous.from().index()][previous.to().index()] = m;
+    }
+
+    let bonus = history_bonus(depth);
+    state.history.update(board.side_to_move, &m, bonus);
+    for tried in quiets_tried {
+        state.history.update(board.side_to_move, tried, -bonus);
+    }
+}
+
+// pruning on the static evaluation is only safe when the position is quiet enough for it
+// to mean something and neither bound is a mate score that it could hide. it is left out
+// at pv nodes, where the exact score is wanted, and at the root, which has to find a move
+fn can_prune(
+    checked: bool,
+    alpha: Score,
+    beta: Score,
+    depth_from_root: u8,
+    excluded: Move,
+) -> bool {
+    let pv_node = beta - alpha > 1;
+    !checked
+        && !pv_node
+        && depth_from_root > 0
+        && excluded.is_null()
+        && alpha.abs() < MATE_BOUND
+        && beta.abs() < MATE_BOUND
+}
+
+// reverse futility pruning, or static null move: when the static evaluation is so far
+// above beta that even losing a margin per ply of depth would not bring it back down,
+// the node is not searched
+fn reverse_futility(
+    depth: u8,
+    beta: Score,
+    state: &SearchState,
+    static_eval: Score,
+) -> Option<Score> {
+    if !state.params.reverse_futility_pruning
+        || depth > REVERSE_FUTILITY_DEPTH
+        || static_eval - state.params.reverse_futility_margin * (depth as Score) < beta
+    {
+        return None;
+    }
+    Some(beta)
+}
+
+// razoring: when the static evaluation is far below alpha close to the horizon, only
+// captures are likely to save the position, so a quiescence search decides whether the
+// node fails low
+fn razor(
+    depth: u8,
+    board: &mut Board,
+    alpha: Score,
+    state: &mut SearchState,
+    static_eval: Score,
+    depth_from_root: u8,
+) -> Option<Score> {
+    if !state.params.razoring
+        || depth > RAZORING_DEPTH
+        || static_eval + state.params.razoring_margin * depth as Score >= alpha
+    {
+        return None;
+    }
+    let eval = search_captures(board, alpha - 1, alpha, state, true, depth_from_root);
+    if eval < alpha {
+        Some(alpha)
+    } else {
+        None
+    }
+}
+
+// futility pruning: at the frontier a quiet move that does not give check can only gain a
+// little, which is not enough when the static evaluation is far below alpha
+fn futile(depth: u8, alpha: Score, state: &SearchState, static_eval: Score) -> bool {
+    state.params.futility_pruning
+        && depth <= FUTILITY_DEPTH
+        && static_eval + state.params.futility_margin * depth as Score <= alpha
+}
+
+// gives the opponent a free move. a position that is still good enough for a cutoff
+// after that almost always has a move that is at least as good, so a search to a
+// reduced depth is enough to prune it. this fails in zugzwang, where every move makes
+// things worse, which is why it is not tried with only pawns left. nor is it tried at pv
+// nodes, where the exact score is wanted rather than a cutoff, or when beta is a mate
+// score, which passing cannot prove
+fn null_move_search(
+    depth: u8,
+    board: &mut Board,
+    alpha: Score,
+    beta: Score,
+    state: &mut SearchState,
+    depth_from_root: u8,
+) -> Option<Score> {
+    let ply = depth_from_root as usize;
+    if !state.params.null_move
+        || !state.null_move_allowed
+        || ply == 0
+        || depth < NULL_MOVE_DEPTH
+        || beta - alpha > 1
+        || beta >= MATE_BOUND
+        || state.moves[ply - 1].is_null()
+        || in_check(board)
+        || !has_non_pawn_material(board, board.side_to_move)
+        || evaluate(board) < beta
+    {
+        return None;
+    }
+
+    let reduction = 2 + depth / 4;
+    let undo = board.make_null_move();
+    state.moves[ply] = Move::NULL;
+    let eval = -absearch(
+        depth.saturating_sub(1 + reduction),
+        board,
+        -beta,
+        -beta + 1,
+        state,
+        depth_from_root + 1,
+    );
+    board.unmake_null_move(undo);
+
+    if eval < beta {
+        return None;
+    }
+    if state.params.null_move_verification && depth >= NULL_MOVE_VERIFICATION_DEPTH {
+        state.null_move_allowed = false;
+        let verified = absearch(depth - reduction, board, beta - 1, beta, state, depth_from_root);
+        state.null_move_allowed = true;
+        if verified < beta {
+            return None;
+        }
+    }
+    Some(beta)
+}
+
+fn has_non_pawn_material(board: &Board, color: Color) -> bool {
+    board.board.iter().any(|piece| {
+        piece.color == color
+            && !matches!(
+                piece.piece_type,
+                PieceTypes::Empty | PieceTypes::Pawn | PieceTypes::King
+            )
+    })
+}
+
+// the root's best move, then the best moves the transposition table has stored after it
+fn principal_variation(
+    board: &mut Board,
+    tt: &TranspositionTable,
+    best_move: Move,
+    depth: u8,
+) -> Vec<Move> {
+    let mut pv = Vec::new();
+    let mut undos = Vec::new();
+    let mut m = best_move;
+    // an entry may belong to a different position with the same hash
+    while !m.is_null() && pv.len() < depth as usize && board.is_legal(&m) {
+        pv.push(m);
+        undos.push(board.push(&m));
+        m = tt.get(board.hash).map_or(Move::NULL, |entry| entry.best_move);
+    }
+    for undo in undos.into_iter().rev() {
+        undo(board);
+    }
+    pv
+}
+
+// after a pseudo-legal move has been pushed, whether the side that made it left its king attacked
+fn leaves_king_in_check(board: &Board) -> bool {
+    let color = !board.side_to_move;
+    is_check(board, board.king_square(color), color)
+}
+
+/// quiescence search: only captures, and on the first ply quiet checks (`checks`), are
+/// searched until the position is quiet enough for the static evaluation to be trusted.
+/// in check every evasion is searched instead, since there is no standing pat there
+pub fn search_captures(
+    board: &mut Board,
+    mut alpha: Score,
+    beta: Score,
+    state: &mut SearchState,
+    checks: bool,
+    depth_from_root: u8,
+) -> Score {
+    state.result.nodes += 1;
+    if state.stopped() {
+        return 0;
+    }
 
-    if legal_captures.len() == 0 {
+    // the quiescence search stores its results at depth 0, so any entry is deep enough
+    let mut tt_move = Move::NULL;
+    if let Some(entry) = probe(state.tt, board.hash, depth_from_root) {
+        match entry.bound {
+            Bound::Exact => return entry.eval.clamp(alpha, beta),
+            Bound::Lower if entry.eval >= beta => return beta,
+            Bound::Upper if entry.eval <= alpha => return alpha,
+            _ => (),
+        }
+        tt_move = entry.best_move;
+    }
+
+    let checked = in_check(board);
+    if depth_from_root as usize >= MAX_PLY - 1 {
         return evaluate(board);
     }
 
-    for m in legal_captures {
-        let undo = board.push(&m);
+    let mut moves;
+    if checked {
+        moves = generate_legal_moves(board);
+        if moves.is_empty() {
+            return REALLY_SMALL_NUMBER + depth_from_root as Score;
+        }
+        order_moves(&mut moves, board, tt_move);
+    } else {
+        // stand pat: the side to move does not have to capture, so the static evaluation
+        // is a lower bound on the score
+        let stand_pat = evaluate(board);
+        if stand_pat >= beta {
+            return beta;
+        }
+        alpha = alpha.max(stand_pat);
 
-        let eval = -search_captures(board, -beta, -alpha);
+        // a capture that loses material in the exchange it starts is not worth looking at
+        // here, and neither is one that cannot bring the score back up to alpha even if
+        // the captured piece comes for free (delta pruning)
+        moves = generate_legal_captures(board);
+        moves.retain(|m| {
+            board.see_ge(m, 0)
+                && (m.is_promotion() || stand_pat + captured_value(board, m) + DELTA_MARGIN > alpha)
+        });
+        order_moves(&mut moves, board, tt_move);
 
+        if checks {
+            let mut quiet_checks = MoveList::new();
+            generate_quiet_checks(board, &mut quiet_checks);
+            for m in quiet_checks {
+                let undo = board.push(&m);
+                if !leaves_king_in_check(board) {
+                    moves.push(m);
+                }
+                undo(board);
+            }
+        }
+    }
+
+    let mut best_move = Move::NULL;
+    for m in moves {
+        let undo = board.push(&m);
+        let eval = -search_captures(board, -beta, -alpha, state, false, depth_from_root + 1);
         undo(board);
+        if state.stopped() {
+            return 0;
+        }
 
         if eval >= beta {
+            store_quiescence(board, state, beta, Bound::Lower, m, depth_from_root);
             return beta;
         }
-
         if eval > alpha {
             alpha = eval;
+            best_move = m;
         }
     }
 
+    let bound = if best_move.is_null() {
+        Bound::Upper
+    } else {
+        Bound::Exact
+    };
+    store_quiescence(board, state, alpha, bound, best_move, depth_from_root);
     alpha
 }
+
+// stored at depth 0, so the table keeps any deeper entry of the current search over these
+fn store_quiescence(
+    board: &Board,
+    state: &SearchState,
+    eval: Score,
+    bound: Bound,
+    best_move: Move,
+    depth_from_root: u8,
+) {
+    let entry = TranspositionEntry {
+        depth: 0,
+        eval,
+        bound,
+        best_move,
+    };
+    store(state.tt, board.hash, entry, depth_from_root);
+}
+
+// the search scores a mate by its distance from the root, but the same position can come up
+// at any ply. the table keeps mate scores as the distance from the position itself and
+// turns them back into distances from the current root when they are read
+fn store(tt: &TranspositionTable, hash: u64, mut entry: TranspositionEntry, depth_from_root: u8) {
+    entry.eval = score_to_tt(entry.eval, depth_from_root);
+    tt.insert(hash, entry);
+}
+
+fn probe(tt: &TranspositionTable, hash: u64, depth_from_root: u8) -> Option<TranspositionEntry> {
+    tt.get(hash).map(|mut entry| {
+        entry.eval = score_from_tt(entry.eval, depth_from_root);
+        entry
+    })
+}
+
+fn score_to_tt(score: Score, depth_from_root: u8) -> Score {
+    if score >= MATE_BOUND {
+        score + depth_from_root as Score
+    } else if score <= -MATE_BOUND {
+        score - depth_from_root as Score
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: Score, depth_from_root: u8) -> Score {
+    if score >= MATE_BOUND {
+        score - depth_from_root as Score
+    } else if score <= -MATE_BOUND {
+        score + depth_from_root as Score
+    } else {
+        score
+    }
+}
+
+fn captured_value(board: &Board, m: &Move) -> Score {
+    let captured = if m.is_ep() {
+        PieceTypes::Pawn
+    } else {
+        board.board[m.to().index()].piece_type
+    };
+    get_value(captured) as Score * 100
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::{
+        iterative_deepening, score_from_tt, score_to_tt, search_captures, EvalResult,
+        SearchParams, SearchState, MATE_BOUND, REALLY_BIG_NUMBER, REALLY_SMALL_NUMBER,
+    };
+    use crate::engine::eval::{evaluate, Score};
+    use crate::engine::transposition::{Bound, TranspositionEntry, TranspositionTable};
+    use crate::game::board::{Board, Move, STARTPOS};
+
+    #[test]
+    fn helper_threads() {
+        let tt = TranspositionTable::new(4);
+        let params = SearchParams {
+            threads: 4,
+            ..SearchParams::default()
+        };
+        let mut board = Board::new("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+        let result = iterative_deepening(&mut board, 5, params, &tt, |_| ());
+        assert_eq!(result.best_move, Move::from_uci("d1d8", &board).unwrap());
+        assert!(result.score > MATE_BOUND);
+        assert!(result.depth >= 5);
+        assert_eq!(result.pv.first(), Some(&result.best_move));
+    }
+
+    #[test]
+    fn multi_pv() {
+        let tt = TranspositionTable::new(4);
+        let params = SearchParams {
+            multi_pv: 3,
+            ..SearchParams::default()
+        };
+        let mut board = Board::new("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+        let result = iterative_deepening(&mut board, 4, params, &tt, |result| {
+            assert_eq!(result.lines.len(), 3);
+        });
+
+        assert_eq!(result.lines[0].pv, result.pv);
+        assert_eq!(result.lines[0].score, result.score);
+        assert_eq!(result.best_move, Move::from_uci("d1d8", &board).unwrap());
+        // only the mate wins, the other lines start with other moves and score less
+        let first_moves: Vec<Move> = result.lines.iter().map(|line| line.pv[0]).collect();
+        assert!(!first_moves[1..].contains(&result.best_move));
+        assert_ne!(first_moves[1], first_moves[2]);
+        assert!(result.lines[1].score < MATE_BOUND);
+        assert!(result.lines[1].score >= result.lines[2].score);
+
+        // no more lines than there are legal moves
+        let mut board = Board::new("7k/8/6Q1/8/8/8/8/K7 b - - 0 1");
+        let result = iterative_deepening(&mut board, 2, params, &tt, |_| ());
+        assert_eq!(result.lines.len(), 1);
+    }
+
+    // a fresh table for every search, so no result depends on what an earlier one left
+    fn search(fen: &str, depth: u8, params: SearchParams) -> EvalResult {
+        let tt = TranspositionTable::new(4);
+        iterative_deepening(&mut Board::new(fen), depth, params, &tt, |_| ())
+    }
+
+    // 1. Rd8+ Rxd8 2. Rxd8#
+    fn assert_finds_mate(params: SearchParams) {
+        let fen = "2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1";
+        let result = search(fen, 5, params);
+        assert_eq!(result.best_move.uci(), "d2d8");
+        assert_eq!(result.score, REALLY_BIG_NUMBER - 3);
+    }
+
+    // white is in zugzwang after 1... Nxd5, which a null move would hide
+    fn assert_finds_zugzwang(params: SearchParams) {
+        let fen = "8/8/1p1r1k2/p1pPN1p1/P3KnP1/1P6/8/3R4 b - - 0 1";
+        assert_eq!(search(fen, 8, params).best_move.uci(), "f4d5");
+    }
+
+    #[test]
+    fn null_move() {
+        let without = SearchParams {
+            null_move: false,
+            ..SearchParams::default()
+        };
+        assert_finds_mate(SearchParams::default());
+        assert_finds_mate(without);
+
+        assert_finds_zugzwang(without);
+
+        let fen = "2kr3r/ppp2ppp/2n5/2b1p3/4P1q1/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 10";
+        let with = search(fen, 6, SearchParams::default());
+        assert!(with.nodes < search(fen, 6, without).nodes);
+    }
+
+    #[test]
+    fn null_move_conditions() {
+        use super::{null_move_search, NULL_MOVE_VERIFICATION_DEPTH};
+
+        // white has to move and lose a pawn, but passing would keep it 150 up
+        let before = Board::new("8/8/1p1r1k2/p1pRN1p1/P3K1P1/1P6/8/8 b - - 0 2");
+        let fen = "8/8/1p2rk2/p1pRN1p1/P3K1P1/1P6/8/8 w - - 1 3";
+        let tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let null_move = |params, depth, alpha, beta| {
+            tt.clear();
+            let mut state = SearchState::new(params, &tt, &stop);
+            state.moves[0] = Move::from_uci("d6e6", &before).unwrap();
+            null_move_search(depth, &mut Board::new(fen), alpha, beta, &mut state, 1)
+        };
+        let unverified = SearchParams {
+            null_move_verification: false,
+            ..SearchParams::default()
+        };
+        let depth = NULL_MOVE_VERIFICATION_DEPTH;
+        assert_eq!(null_move(unverified, depth, 149, 150), Some(150));
+        // a normal search finds the zugzwang, but only from the verification depth on
+        assert_eq!(null_move(SearchParams::default(), depth, 149, 150), None);
+        assert_eq!(null_move(SearchParams::default(), depth - 1, 149, 150), Some(150));
+
+        // not at pv nodes
+        assert_eq!(null_move(unverified, depth, 100, 150), None);
+        assert_eq!(null_move(unverified, depth, 100, 101), Some(101));
+    }
+
+    #[test]
+    fn extensions() {
+        use super::{is_singular, SINGULAR_DEPTH};
+
+        let without = SearchParams {
+            extensions: false,
+            ..SearchParams::default()
+        };
+        assert_finds_mate(without);
+
+        // 1. Nf7+ Kg8 2. Nh6+ Kh8 3. Qg8+ Rxg8 4. Nf7#, seven plies, but four of them checks
+        let fen = "1r5k/6pp/8/6N1/2Q5/8/6PP/6K1 w - - 0 1";
+        let result = search(fen, 3, SearchParams::default());
+        assert_eq!(result.best_move.uci(), "g5f7");
+        assert_eq!(result.score, REALLY_BIG_NUMBER - 7);
+        assert!(search(fen, 3, without).score < MATE_BOUND);
+
+        let singular = |fen: &str| {
+            let found = search(fen, SINGULAR_DEPTH, SearchParams::default());
+            let entry = TranspositionEntry {
+                depth: SINGULAR_DEPTH,
+                eval: found.score,
+                bound: Bound::Exact,
+                best_move: found.best_move,
+            };
+            let tt = TranspositionTable::new(1);
+            let stop = AtomicBool::new(false);
+            let mut state = SearchState::new(SearchParams::default(), &tt, &stop);
+            state.root_depth = SINGULAR_DEPTH;
+            is_singular(SINGULAR_DEPTH, &mut Board::new(fen), entry, &mut state, 1)
+        };
+        // only taking the queen back keeps the material level
+        assert!(singular("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1"));
+        // a rook up, and most moves keep it
+        assert!(!singular("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn late_move_reductions() {
+        use super::{late_move_reduction, reduction_table, LMR_DEPTH, LMR_FULL_DEPTH_MOVES};
+
+        let table = reduction_table();
+        assert!(table[0].iter().all(|&r| r == 0));
+        assert!(table[1].iter().all(|&r| r == 0));
+        assert!(table.iter().all(|row| row[0] == 0 && row[1] == 0));
+        assert_eq!((table[3][4], table[6][10], table[8][20], table[63][63]), (1, 2, 3, 8));
+        // more for deeper searches and later moves
+        for depth in 1..64 {
+            for move_number in 1..64 {
+                assert!(table[depth][move_number] >= table[depth - 1][move_number]);
+                assert!(table[depth][move_number] >= table[depth][move_number - 1]);
+            }
+        }
+
+        let board = Board::new("4k3/8/8/3p4/4P3/8/8/R3K3 w - - 0 1");
+        let quiet = Move::from_uci("a1a2", &board).unwrap();
+        let capture = Move::from_uci("e4d5", &board).unwrap();
+        let check = Move::from_uci("a1a8", &board).unwrap();
+        let tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let state = SearchState::new(SearchParams::default(), &tt, &stop);
+        let late = LMR_FULL_DEPTH_MOVES + 1;
+        assert_eq!(late_move_reduction(&state, &quiet, 8, 20, false, false), 3);
+        assert_eq!(late_move_reduction(&state, &quiet, LMR_DEPTH, late, false, false), 1);
+        // the moves searched first, the pv move among them, are never reduced
+        for move_number in 1..=LMR_FULL_DEPTH_MOVES {
+            assert_eq!(late_move_reduction(&state, &quiet, 8, move_number, false, false), 0);
+        }
+        assert_eq!(late_move_reduction(&state, &quiet, LMR_DEPTH - 1, 20, false, false), 0);
+        assert_eq!(late_move_reduction(&state, &capture, 8, 20, false, false), 0);
+        assert_eq!(late_move_reduction(&state, &check, 8, 20, false, true), 0);
+        assert_eq!(late_move_reduction(&state, &quiet, 8, 20, true, false), 0);
+
+        let without = SearchParams {
+            late_move_reductions: false,
+            ..SearchParams::default()
+        };
+        let state = SearchState::new(without, &tt, &stop);
+        assert_eq!(late_move_reduction(&state, &quiet, 8, 20, false, false), 0);
+        assert_finds_mate(without);
+    }
+
+    #[test]
+    fn quiet_move_ordering() {
+        use super::{absearch, update_quiet_heuristics};
+        use crate::game::movelist::MoveList;
+
+        let board = Board::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let parse = |uci| Move::from_uci(uci, &board).unwrap();
+        let (cutoff, other) = (parse("f1b5"), parse("f1c4"));
+        let mut tried = MoveList::new();
+        tried.push(parse("a2a3"));
+        tried.push(parse("h2h3"));
+        let before = Board::new("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+        let previous = Move::from_uci("b8c6", &before).unwrap();
+
+        let tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let mut state = SearchState::new(SearchParams::default(), &tt, &stop);
+        update_quiet_heuristics(&board, &mut state, cutoff, &tried, 5, 2, previous);
+
+        // the move that cut off becomes the first killer and the countermove to the move
+        // before it, and gains history that the moves tried before it lose
+        assert_eq!(state.killers[2], [cutoff, Move::NULL]);
+        assert_eq!(state.killers[1], [Move::NULL; 2]);
+        let (from, to) = (previous.from().index(), previous.to().index());
+        assert_eq!(state.countermoves[from][to], cutoff);
+        assert_eq!(state.history.get(board.side_to_move, &cutoff), 25);
+        for m in tried.iter() {
+            assert_eq!(state.history.get(board.side_to_move, m), -25);
+        }
+        assert_eq!(state.history.get(!board.side_to_move, &cutoff), 0);
+
+        // a second killer pushes the first one back, and the same one twice does not fill
+        // both slots
+        update_quiet_heuristics(&board, &mut state, other, &MoveList::new(), 5, 2, Move::NULL);
+        assert_eq!(state.killers[2], [other, cutoff]);
+        update_quiet_heuristics(&board, &mut state, other, &MoveList::new(), 5, 2, Move::NULL);
+        assert_eq!(state.killers[2], [other, cutoff]);
+        assert_eq!(state.countermoves[from][to], cutoff);
+        assert_eq!(state.history.get(board.side_to_move, &other), 50);
+
+        // a search fills them in, unless they are turned off
+        let without = SearchParams {
+            quiet_move_ordering: false,
+            ..SearchParams::default()
+        };
+        for (params, used) in [(SearchParams::default(), true), (without, false)] {
+            tt.clear();
+            let mut state = SearchState::new(params, &tt, &stop);
+            let (min, max) = (REALLY_SMALL_NUMBER * 10, REALLY_BIG_NUMBER * 10);
+            absearch(5, &mut board.clone(), min, max, &mut state, 0);
+            let killers = state.killers.iter().flatten().any(|m| !m.is_null());
+            assert_eq!(killers, used);
+        }
+        assert_finds_mate(without);
+    }
+
+    #[test]
+    fn static_pruning() {
+        use super::{
+            can_prune, futile, razor, reverse_futility, FUTILITY_DEPTH, RAZORING_DEPTH,
+            REVERSE_FUTILITY_DEPTH,
+        };
+
+        // only at null window nodes below the root, out of check, away from mate scores
+        // and outside a singular search
+        let m = Move::from_uci("e2e4", &Board::new(STARTPOS)).unwrap();
+        assert!(can_prune(false, 99, 100, 1, Move::NULL));
+        assert!(!can_prune(true, 99, 100, 1, Move::NULL));
+        assert!(!can_prune(false, 50, 100, 1, Move::NULL));
+        assert!(!can_prune(false, 99, 100, 0, Move::NULL));
+        assert!(!can_prune(false, 99, 100, 1, m));
+        assert!(!can_prune(false, MATE_BOUND, MATE_BOUND + 1, 1, Move::NULL));
+        assert!(!can_prune(false, -MATE_BOUND - 1, -MATE_BOUND, 1, Move::NULL));
+
+        let tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let all = SearchParams::default();
+        let state = SearchState::new(all, &tt, &stop);
+        let off = SearchState::new(
+            SearchParams {
+                reverse_futility_pruning: false,
+                razoring: false,
+                futility_pruning: false,
+                ..all
+            },
+            &tt,
+            &stop,
+        );
+
+        // reverse futility: beta plus 80 a ply
+        assert_eq!(reverse_futility(3, 0, &state, 240), Some(0));
+        assert_eq!(reverse_futility(3, 0, &state, 239), None);
+        assert_eq!(reverse_futility(REVERSE_FUTILITY_DEPTH, 0, &state, 480), Some(0));
+        assert_eq!(reverse_futility(REVERSE_FUTILITY_DEPTH + 1, 0, &state, 10_000), None);
+        assert_eq!(reverse_futility(3, 0, &off, 10_000), None);
+        let narrow = SearchState::new(
+            SearchParams {
+                reverse_futility_margin: 10,
+                ..all
+            },
+            &tt,
+            &stop,
+        );
+        assert_eq!(reverse_futility(3, 0, &narrow, 30), Some(0));
+
+        // futility: alpha less 100 a ply
+        assert!(futile(2, 0, &state, -200));
+        assert!(!futile(2, 0, &state, -199));
+        assert!(!futile(FUTILITY_DEPTH + 1, 0, &state, -10_000));
+        assert!(!futile(2, 0, &off, -10_000));
+
+        // razoring: alpha less 200 a ply, and a quiescence search that agrees
+        let mut state = SearchState::new(all, &tt, &stop);
+        let mut board = Board::new("4k3/8/8/8/8/8/8/R3K3 b - - 0 1");
+        let eval = evaluate(&board);
+        let alpha = eval + 401;
+        assert_eq!(razor(2, &mut board, alpha, &mut state, eval, 1), Some(alpha));
+        assert_eq!(razor(2, &mut board, alpha - 1, &mut state, eval, 1), None);
+        let alpha = eval + 200 * RAZORING_DEPTH as Score + 1;
+        assert_eq!(razor(RAZORING_DEPTH, &mut board, alpha, &mut state, eval, 1), Some(alpha));
+        assert_eq!(razor(RAZORING_DEPTH + 1, &mut board, 10_000, &mut state, eval, 1), None);
+        let mut off = SearchState::new(off.params, &tt, &stop);
+        assert_eq!(razor(1, &mut board, 10_000, &mut off, eval, 1), None);
+        // the knight takes the rook back, which the quiescence search sees
+        let mut board = Board::new("4k3/8/8/8/8/1n6/8/R3K2R b - - 0 1");
+        let eval = evaluate(&board);
+        assert_eq!(razor(1, &mut board, eval + 201, &mut state, eval, 1), None);
+
+        // each kind of pruning on its own still finds the mate
+        for params in [
+            SearchParams {
+                reverse_futility_pruning: false,
+                ..all
+            },
+            SearchParams {
+                razoring: false,
+                ..all
+            },
+            SearchParams {
+                futility_pruning: false,
+                ..all
+            },
+        ] {
+            assert_finds_mate(params);
+        }
+    }
+
+    #[test]
+    fn aspiration_windows() {
+        use super::{absearch, aspiration_search};
+
+        let (min, max) = (REALLY_SMALL_NUMBER * 10, REALLY_BIG_NUMBER * 10);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/1p1r1k2/p1pPN1p1/P3KnP1/1P6/8/3R4 b - - 0 1",
+        ] {
+            let stop = AtomicBool::new(false);
+            let tt = TranspositionTable::new(4);
+            let mut state = SearchState::new(SearchParams::default(), &tt, &stop);
+            let score = absearch(5, &mut Board::new(fen), min, max, &mut state, 0);
+            let best_move = state.result.best_move;
+
+            // a guess far below the score fails high and one far above it fails low, and
+            // the windows widen until the search ends up where the full window did
+            for guess in [score - 500, score + 500] {
+                let tt = TranspositionTable::new(4);
+                let mut state = SearchState::new(SearchParams::default(), &tt, &stop);
+                state.result.score = guess;
+                let found = aspiration_search(5, &mut Board::new(fen), &mut state);
+                assert_eq!((found, state.result.best_move), (score, best_move), "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn mate_scores_in_table() {
+        for score in [REALLY_BIG_NUMBER - 7, REALLY_SMALL_NUMBER + 4, 350, -MATE_BOUND + 1] {
+            for ply in [0, 3, 40] {
+                assert_eq!(score_from_tt(score_to_tt(score, ply), ply), score);
+            }
+        }
+        // mated three plies below a node at ply 5 is mated three plies below it at ply 1 too
+        let stored = score_to_tt(REALLY_SMALL_NUMBER + 8, 5);
+        assert_eq!(stored, REALLY_SMALL_NUMBER + 3);
+        assert_eq!(score_from_tt(stored, 1), REALLY_SMALL_NUMBER + 4);
+
+        // 1. Rd8+ Rxd8 2. Rxd8#, then the same table used from the position after 1. Rd8+,
+        // where the entries of the first search are a ply closer to the root
+        let tt = TranspositionTable::new(4);
+        let mut board = Board::new("2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1");
+        let result = iterative_deepening(&mut board, 5, SearchParams::default(), &tt, |_| ());
+        assert_eq!(result.score, REALLY_BIG_NUMBER - 3);
+
+        let check = Move::from_uci("d2d8", &board).unwrap();
+        let _ = board.push(&check);
+        let result = iterative_deepening(&mut board, 2, SearchParams::default(), &tt, |_| ());
+        assert_eq!(result.score, REALLY_SMALL_NUMBER + 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn eval_result_json() {
+        use super::{absearch, EvalResultSeed};
+        use serde::de::DeserializeSeed;
+
+        let read = |json: &str, board: &Board| {
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            EvalResultSeed(board).deserialize(&mut deserializer)
+        };
+
+        let mut board = Board::new("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let mut state = SearchState::new(SearchParams::default(), &tt, &stop);
+        absearch(
+            2,
+            &mut board,
+            REALLY_SMALL_NUMBER * 10,
+            REALLY_BIG_NUMBER * 10,
+            &mut state,
+            0,
+        );
+
+        let json = serde_json::to_string(&state.result).unwrap();
+        let expected = r#"{"best_move":"a1a8","score":99999,"pv":["a1a8"],"depth":2,"nodes":"#;
+        assert!(json.starts_with(expected), "{}", json);
+
+        // longer lines, each played out from the root to be read back
+        let params = SearchParams {
+            multi_pv: 3,
+            ..SearchParams::default()
+        };
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::new(fen);
+        let result = iterative_deepening(&mut board, 4, params, &tt, |_| ());
+        assert!(result.pv.len() > 1);
+        let json = serde_json::to_string(&result).unwrap();
+        let round_trip = read(&json, &board).unwrap();
+        assert_eq!(round_trip.best_move, result.best_move);
+        assert_eq!(round_trip.score, result.score);
+        assert_eq!(round_trip.pv, result.pv);
+        assert_eq!(round_trip.depth, result.depth);
+        assert_eq!(round_trip.nodes, result.nodes);
+        assert_eq!(round_trip.lines.len(), 3);
+        for (read, line) in round_trip.lines.iter().zip(&result.lines) {
+            assert_eq!((read.score, &read.pv), (line.score, &line.pv));
+        }
+        assert_eq!(serde_json::to_string(&round_trip).unwrap(), json);
+
+        // a pv that cannot be played from the board is an error
+        assert!(read(&json, &Board::new("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")).is_err());
+        let illegal = json.replacen(&format!("\"{}\"", result.pv[1].uci()), "\"e1e8\"", 1);
+        assert!(read(&illegal, &board).is_err());
+    }
+
+    // the score and node count of a quiescence search from the root
+    fn quiesce(fen: &str, alpha: Score, beta: Score, tt: &TranspositionTable) -> (Score, u64) {
+        let stop = AtomicBool::new(false);
+        let mut state = SearchState::new(SearchParams::default(), tt, &stop);
+        let score = search_captures(&mut Board::new(fen), alpha, beta, &mut state, false, 0);
+        (score, state.result.nodes)
+    }
+
+    #[test]
+    fn quiescence_stand_pat() {
+        let tt = TranspositionTable::new(1);
+        // a queen up, the static evaluation is already above beta
+        let fen = "4k3/8/8/8/8/8/8/Q3K3 w - - 0 1";
+        assert_eq!(quiesce(fen, REALLY_SMALL_NUMBER, 100, &tt), (100, 1));
+        // with no captures the static evaluation is the score
+        let score = evaluate(&Board::new(fen));
+        assert_eq!(quiesce(fen, REALLY_SMALL_NUMBER, REALLY_BIG_NUMBER, &tt).0, score);
+    }
+
+    #[test]
+    fn quiescence_pruning() {
+        let tt = TranspositionTable::new(1);
+
+        // a hanging queen is neither a losing capture nor too small to reach alpha
+        let fen = "4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1";
+        let stand_pat = evaluate(&Board::new(fen));
+        let (score, nodes) = quiesce(fen, stand_pat + 500, REALLY_BIG_NUMBER, &tt);
+        assert!(score > stand_pat + 800);
+        assert!(nodes > 1);
+
+        // a free pawn is searched, unless alpha is out of its reach (delta pruning)
+        tt.clear();
+        let fen = "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1";
+        let stand_pat = evaluate(&Board::new(fen));
+        assert!(quiesce(fen, stand_pat + 50, REALLY_BIG_NUMBER, &tt).1 > 1);
+        tt.clear();
+        assert_eq!(
+            quiesce(fen, stand_pat + 400, REALLY_BIG_NUMBER, &tt),
+            (stand_pat + 400, 1)
+        );
+
+        // the queen takes a free pawn, but not one a pawn defends (see pruning)
+        tt.clear();
+        let fen = "4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1";
+        let stand_pat = evaluate(&Board::new(fen));
+        let (score, nodes) = quiesce(fen, REALLY_SMALL_NUMBER, REALLY_BIG_NUMBER, &tt);
+        assert!(score > stand_pat);
+        assert!(nodes > 1);
+        tt.clear();
+        let fen = "4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1";
+        let stand_pat = evaluate(&Board::new(fen));
+        assert_eq!(
+            quiesce(fen, REALLY_SMALL_NUMBER, REALLY_BIG_NUMBER, &tt),
+            (stand_pat, 1)
+        );
+    }
+
+    #[test]
+    fn quiescence_table() {
+        let tt = TranspositionTable::new(1);
+        let fen = "4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1";
+        let board = Board::new(fen);
+        let entry = |eval, bound| TranspositionEntry {
+            depth: 0,
+            eval,
+            bound,
+            best_move: Move::NULL,
+        };
+
+        // an exact entry is the score, whatever the position holds
+        tt.insert(board.hash, entry(123, Bound::Exact));
+        assert_eq!(quiesce(fen, REALLY_SMALL_NUMBER, REALLY_BIG_NUMBER, &tt), (123, 1));
+        // a lower bound cuts off at beta, but not below it
+        tt.insert(board.hash, entry(300, Bound::Lower));
+        assert_eq!(quiesce(fen, REALLY_SMALL_NUMBER, 200, &tt), (200, 1));
+        assert!(quiesce(fen, REALLY_SMALL_NUMBER, REALLY_BIG_NUMBER, &tt).1 > 1);
+
+        // the search stores what it found, which the next one reads
+        tt.clear();
+        let score = quiesce(fen, REALLY_SMALL_NUMBER, REALLY_BIG_NUMBER, &tt).0;
+        let stored = tt.get(board.hash).unwrap();
+        assert_eq!((stored.eval, stored.bound), (score, Bound::Exact));
+        assert_eq!(stored.best_move, Move::from_uci("e4d5", &board).unwrap());
+        assert_eq!(quiesce(fen, REALLY_SMALL_NUMBER, REALLY_BIG_NUMBER, &tt), (score, 1));
+    }
+
+    #[test]
+    fn quiescence_in_check() {
+        let tt = TranspositionTable::new(1);
+        // a queen up, but in check from a knight that forks the queen, so standing pat
+        // would cut off at beta where every evasion loses the queen
+        let fen = "4k3/2N5/q7/8/8/8/8/4K3 b - - 0 1";
+        assert!(evaluate(&Board::new(fen)) > 0);
+        assert!(quiesce(fen, REALLY_SMALL_NUMBER, 0, &tt).0 < 0);
+
+        // no evasions is mate, not the static evaluation
+        let fen = "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1";
+        assert_eq!(
+            quiesce(fen, REALLY_SMALL_NUMBER, REALLY_BIG_NUMBER, &tt).0,
+            REALLY_SMALL_NUMBER
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Static exchange evaluation: what a capture wins once all the recaptures on its
+//! square have been played out.
+
+use crate::game::{
+    board::{Board, Move, Piece, PieceTypes, EMPTY_PIECE},
+    color::Color,
+    movegen::square_attackers,
+    square::Square,
+};
+
+use super::{eval::Score, search::get_value};
+
+// the first move and every capture after it, there are only 32 pieces to capture with
+const MAX_EXCHANGES: usize = 34;
+
+fn value(piece_type: PieceTypes) -> Score {
+    get_value(piece_type) as Score * 100
+}
+
+impl Board {
+    /// the material the side to move ends up with after `m` and the best sequence of
+    /// captures on its destination, where either side can stop capturing when it would
+    /// lose by going on. pins and checks are ignored
+    pub fn see(&self, m: &Move) -> Score {
+        if m.is_null() || m.is_castle() {
+            return 0;
+        }
+
+        let mut squares = self.board;
+        let target = m.to();
+        let mut gain = [0; MAX_EXCHANGES];
+
+        let moving = squares[m.from().index()];
+        gain[0] = if m.is_ep() {
+            let captured = target.backward(moving.color).unwrap();
+            squares[captured.index()] = EMPTY_PIECE;
+            value(PieceTypes::Pawn)
+        } else {
+            value(squares[target.index()].piece_type)
+        };
+        let mut on_target = moving;
+        if m.is_promotion() {
+            on_target.piece_type = m.promotion();
+            gain[0] += value(m.promotion()) - value(PieceTypes::Pawn);
+        }
+        squares[target.index()] = on_target;
+        squares[m.from().index()] = EMPTY_PIECE;
+
+        // sliders behind a piece that has captured show up once it is gone from the board
+        let attackers_of = |squares: &[Piece; 64], color: Color| {
+            square_attackers(squares, &self.precomputed_move_data, target, !color)
+        };
+
+        let mut side = !moving.color;
+        let mut depth = 0;
+        loop {
+            let attackers = attackers_of(&squares, side);
+            let Some(from) = least_valuable(&squares, attackers) else {
+                break;
+            };
+            // a king cannot take a piece that is still defended
+            let attacker = squares[from.index()];
+            if attacker.piece_type == PieceTypes::King && attackers_of(&squares, !side) != 0 {
+                break;
+            }
+
+            // what the capturing side has if the exchange stops here
+            depth += 1;
+            gain[depth] = value(on_target.piece_type) - gain[depth - 1];
+
+            on_target = attacker;
+            squares[target.index()] = attacker;
+            squares[from.index()] = EMPTY_PIECE;
+            side = !side;
+        }
+
+        // each side only captures when that is better than stopping
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+
+    /// whether `m` wins at least `threshold` by static exchange evaluation
+    pub fn see_ge(&self, m: &Move, threshold: Score) -> bool {
+        self.see(m) >= threshold
+    }
+}
+
+fn least_valuable(squares: &[Piece; 64], attackers: u64) -> Option<Square> {
+    Square::all()
+        .filter(|square| attackers & square.bit() != 0)
+        .min_by_key(|square| value(squares[square.index()].piece_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::board::{Board, Move};
+
+    fn see(fen: &str, m: &str) -> i32 {
+        let board = Board::new(fen);
+        board.see(&Move::from_uci(m, &board).unwrap())
+    }
+
+    #[test]
+    fn exchanges() {
+        // a free pawn
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        // the queen takes a pawn defended by a pawn
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"), -800);
+        // rook takes a knight defended by a rook, and a second rook behind it recaptures
+        assert_eq!(see("3r2k1/8/8/3n4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 300);
+        // the queen behind the bishop joins in once the bishop has captured
+        assert_eq!(
+            see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"),
+            -200
+        );
+        // the king recaptures, but not on a square that is still defended
+        assert_eq!(see("4k3/4p3/8/8/8/8/4R3/4K3 w - - 0 1", "e2e7"), -400);
+        assert_eq!(see("4k3/4p3/8/8/8/8/4R3/4RK2 w - - 0 1", "e2e7"), 100);
+        // en passant and a quiet move
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        assert_eq!(see("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a2"), 0);
+    }
+}
@@ -0,0 +1,227 @@
+//! The transposition table, shared by every thread of a search without locks.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::game::{board::Move, square::Square};
+
+use super::eval::Score;
+
+/// the table size when none is given, in megabytes
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// how the stored eval relates to the real score of the position
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Bound {
+    /// the search found the exact score
+    Exact,
+    /// the search failed high, the score is at least eval
+    Lower,
+    /// no move reached alpha, the score is at most eval
+    Upper,
+}
+
+/// what the search remembers about a position
+#[derive(Clone, Copy, Debug)]
+pub struct TranspositionEntry {
+    /// how many plies deep the position was searched
+    pub depth: u8,
+    /// the score, with mates counted from this position
+    pub eval: Score,
+    /// how eval relates to the real score
+    pub bound: Bound,
+    /// the move that was best or caused the cutoff, Move::NULL when there was none
+    pub best_move: Move,
+}
+
+// threads write the two words of a slot one after the other, so another thread can read
+// the key of one entry with the data of another. the key is stored xored with the data,
+// which only gives back the hash when both halves belong to the same write
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// a fixed number of slots indexed by the low bits of the zobrist hash, one entry each
+pub struct TranspositionTable {
+    slots: Box<[Slot]>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// the largest power of two number of slots that fits in `megabytes`
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let bytes = megabytes.max(1) * 1024 * 1024;
+        let fits = bytes / std::mem::size_of::<Slot>();
+        let len = 1 << (usize::BITS - 1 - fits.leading_zeros());
+        TranspositionTable {
+            slots: (0..len)
+                .map(|_| Slot {
+                    key: AtomicU64::new(0),
+                    data: AtomicU64::new(0),
+                })
+                .collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// the number of slots
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// never true, there is always at least one slot
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// empties every slot, for a new game
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// marks the entries written so far as left over from an earlier search
+    pub fn new_search(&self) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store((generation + 1) & GENERATION_MASK, Ordering::Relaxed);
+    }
+
+    /// the entry for `hash`, if the slot holds that position
+    pub fn get(&self, hash: u64) -> Option<TranspositionEntry> {
+        let slot = self.slot(hash);
+        let data = slot.data.load(Ordering::Relaxed);
+        let key = slot.key.load(Ordering::Relaxed);
+        if data == 0 || key ^ data != hash {
+            return None;
+        }
+        Some(unpack(data))
+    }
+
+    /// an entry written during the current search is only replaced, by the same position
+    /// or another one, with one searched at least as deep. entries from an earlier search
+    /// are always replaced
+    pub fn insert(&self, hash: u64, entry: TranspositionEntry) {
+        let slot = self.slot(hash);
+        let generation = self.generation.load(Ordering::Relaxed) as u64;
+        let existing = slot.data.load(Ordering::Relaxed);
+        if existing != 0 && existing >> 58 == generation && (existing >> 48) as u8 > entry.depth {
+            return;
+        }
+        let data = pack(&entry) | generation << 58;
+        slot.data.store(data, Ordering::Relaxed);
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        &self.slots[hash as usize & (self.slots.len() - 1)]
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+// the generation is kept in the top 6 bits of the data and wraps around
+const GENERATION_MASK: u8 = 0x3f;
+
+// bits 0-15 the move, 16-47 the eval, 48-55 the depth and 56-57 the bound plus one, so no
+// entry packs to 0, which marks an empty slot. insert adds the generation at 58-63
+fn pack(entry: &TranspositionEntry) -> u64 {
+    let m = &entry.best_move;
+    let move_bits = m.from().index() as u64 | (m.to().index() as u64) << 6 | (m.flags() as u64) << 12;
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    move_bits | (entry.eval as u32 as u64) << 16 | (entry.depth as u64) << 48 | bound << 56
+}
+
+fn unpack(data: u64) -> TranspositionEntry {
+    let best_move = Move::new(
+        Square::from_index((data & 0x3f) as usize),
+        Square::from_index((data >> 6 & 0x3f) as usize),
+        (data >> 12 & 0xf) as u16,
+    );
+    let bound = match data >> 56 & 0b11 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    TranspositionEntry {
+        depth: (data >> 48) as u8,
+        eval: (data >> 16) as u32 as Score,
+        bound,
+        best_move,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bound, TranspositionEntry, TranspositionTable};
+    use crate::game::board::{Board, Move, STARTPOS};
+
+    #[test]
+    fn size() {
+        // 16 bytes a slot
+        assert_eq!(TranspositionTable::new(1).len(), 1 << 16);
+        assert_eq!(TranspositionTable::new(3).len(), 1 << 17);
+        assert_eq!(TranspositionTable::new(16).len(), 1 << 20);
+        assert_eq!(TranspositionTable::new(0).len(), 1 << 16);
+    }
+
+    #[test]
+    fn store_and_verify() {
+        let board = Board::new(STARTPOS);
+        let table = TranspositionTable::new(1);
+        let m = Move::from_uci("g1f3", &board).unwrap();
+        let entry = |depth, eval| TranspositionEntry {
+            depth,
+            eval,
+            bound: Bound::Lower,
+            best_move: m,
+        };
+
+        table.insert(board.hash, entry(5, -1234));
+        let stored = table.get(board.hash).unwrap();
+        assert_eq!((stored.depth, stored.eval, stored.bound), (5, -1234, Bound::Lower));
+        assert_eq!(stored.best_move, m);
+
+        // shallower results for the same position do not replace deeper ones
+        table.insert(board.hash, entry(2, 50));
+        assert_eq!(table.get(board.hash).unwrap().depth, 5);
+
+        // neither do shallower results for another position in the same slot
+        let other = board.hash ^ (table.len() as u64) << 1;
+        table.insert(other, entry(0, 7));
+        assert!(table.get(other).is_none());
+        assert_eq!(table.get(board.hash).unwrap().eval, -1234);
+
+        // once the next search starts they do, and the first position is gone
+        table.new_search();
+        table.insert(other, entry(0, 7));
+        assert_eq!(table.get(other).unwrap().eval, 7);
+        assert!(table.get(board.hash).is_none());
+
+        // the same position, or a deeper one, replaces an entry of the current search
+        table.insert(other, entry(0, 8));
+        assert_eq!(table.get(other).unwrap().eval, 8);
+        table.insert(board.hash, entry(1, 9));
+        assert_eq!(table.get(board.hash).unwrap().eval, 9);
+
+        // the generation wraps around without touching the rest of the entry
+        for _ in 0..64 {
+            table.new_search();
+        }
+        table.insert(board.hash, entry(3, -5));
+        let stored = table.get(board.hash).unwrap();
+        assert_eq!((stored.depth, stored.eval, stored.bound), (3, -5, Bound::Lower));
+
+        table.clear();
+        assert!(table.get(other).is_none());
+    }
+}
@@ -1,106 +1,328 @@
-use std::{fmt::{Display, Debug}, collections::HashMap};
-use rand::prelude::random;
+//! The board, moves and the rules for making them.
 
-use crate::engine::hash::{init_table, generate_hash, WPAWN, BPAWN, BBISHOP, WBISHOP, WKNIGHT, BKNIGHT, WROOK, BROOK, WQUEEN, BQUEEN, WKING, BKING};
+use std::{fmt::{Display, Debug}, str::FromStr};
 
-use super::movegen::{MoveData, compute_distances};
+use crate::engine::hash::{generate_hash, piece_index, ZOBRIST};
 
+use super::movegen::{
+    compute_distances, generate_legal_moves, generate_piece_moves, square_attackers, MoveData,
+};
+use super::movelist::MoveList;
+use super::render::RenderOptions;
+use super::color::Color;
+use super::square::{File, ParseSquareError, Rank, Square};
+
+/// the fen of the standard starting position
 pub const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-pub const SQUARES: [&str; 64] = [
-    "a8","b8","c8","d8","e8","f8","g8","h8",
-    "a7","b7","c7","d7","e7","f7","g7","h7",
-    "a6","b6","c6","d6","e6","f6","g6","h6",
-    "a5","b5","c5","d5","e5","f5","g5","h5",
-    "a4","b4","c4","d4","e4","f4","g4","h4",
-    "a3","b3","c3","d3","e3","f3","g3","h3",
-    "a2","b2","c2","d2","e2","f2","g2","h2",
-    "a1","b1","c1","d1","e1","f1","g1","h1",
-    ];
 
+/// the kinds of piece, with Empty for an empty square
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceTypes {
+    /// no piece
     Empty = 0,
+    /// a pawn
     Pawn = 1,
+    /// a bishop
     Bishop = 2,
+    /// a knight
     Knight = 3,
+    /// a rook
     Rook = 4,
+    /// a queen
     Queen = 5,
+    /// a king
     King = 6,
 }
 
-#[derive(Copy, Clone, PartialEq)]
-pub struct Move {
-    pub from: u8,
-    pub to: u8,
-    pub promotion: PieceTypes,
+/// from square in bits 0-5, to square in bits 6-11 and the move flags in bits 12-15
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Move(u16);
+
+/// a move that captures nothing and is none of the moves below
+pub const QUIET: u16 = 0;
+/// a pawn moving two squares
+pub const DOUBLE_PUSH: u16 = 1;
+/// castling with the rook on the king side
+pub const KING_CASTLE: u16 = 2;
+/// castling with the rook on the queen side
+pub const QUEEN_CASTLE: u16 = 3;
+/// a capture of the piece on the to square
+pub const CAPTURE: u16 = 4;
+/// a capture en passant
+pub const EP_CAPTURE: u16 = 5;
+/// a promotion to a knight
+pub const KNIGHT_PROMOTION: u16 = 8;
+/// a promotion to a bishop
+pub const BISHOP_PROMOTION: u16 = 9;
+/// a promotion to a rook
+pub const ROOK_PROMOTION: u16 = 10;
+/// a promotion to a queen
+pub const QUEEN_PROMOTION: u16 = 11;
+/// a capture that promotes to a knight
+pub const KNIGHT_PROMOTION_CAPTURE: u16 = 12;
+/// a capture that promotes to a bishop
+pub const BISHOP_PROMOTION_CAPTURE: u16 = 13;
+/// a capture that promotes to a rook
+pub const ROOK_PROMOTION_CAPTURE: u16 = 14;
+/// a capture that promotes to a queen
+pub const QUEEN_PROMOTION_CAPTURE: u16 = 15;
+
+const PROMOTION_FLAG: u16 = 0b1000;
+const CAPTURE_FLAG: u16 = 0b0100;
+
+/// a move that cannot be played on the board it was tried on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IllegalMoveError(pub Move);
+
+impl Display for IllegalMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // king-takes-rook, a castle that cannot be played has no square for the king to land on
+        write!(f, "illegal move: {}", self.0.to_uci(true))
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct TranspositionEntry {
-    pub depth: u8,
-    pub eval: f32,
+impl std::error::Error for IllegalMoveError {}
+
+/// why a string is not a uci move
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseMoveError {
+    /// not 4 or 5 characters
+    Length(String),
+    /// one of the squares is not a square
+    Square(ParseSquareError),
+    /// a promotion that is missing, not a piece or not allowed
+    Promotion(String),
+}
+
+impl Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseMoveError::Length(m) => {
+                write!(f, "invalid move {:?}: expected 4 or 5 characters", m)
+            }
+            ParseMoveError::Square(e) => write!(f, "invalid move: {}", e),
+            ParseMoveError::Promotion(m) => write!(f, "invalid promotion in move {:?}", m),
+        }
+    }
+}
+
+impl std::error::Error for ParseMoveError {}
+
+impl From<ParseSquareError> for ParseMoveError {
+    fn from(e: ParseSquareError) -> Self {
+        ParseMoveError::Square(e)
+    }
+}
+
+/// why a string is not a fen
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseFenError(pub String);
+
+impl Display for ParseFenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid fen: {:?}", self.0)
+    }
 }
 
+impl std::error::Error for ParseFenError {}
+
+/// a chess position with everything needed to continue the game from it:
+/// side to move, castling rights, en passant square, move clocks and the
+/// zobrist hash. moves are made with push, which returns the closure that
+/// takes them back
 #[derive(Clone, Debug)]
 pub struct Board {
+    /// the pieces by square index, a8 first
     pub board: [Piece; 64],
 
-    pub wtomove: bool,
+    /// the side whose turn it is
+    pub side_to_move: Color,
 
-    pub white_ks: bool,
-    pub white_qs: bool,
-    pub black_ks: bool,
-    pub black_qs: bool,
-    pub enpassant_square: u8,
+    /// castling rights hold the start square of the rook that may still castle
+    pub white_ks: Option<Square>,
+    /// as white_ks, for the queen side
+    pub white_qs: Option<Square>,
+    /// as white_ks, for black
+    pub black_ks: Option<Square>,
+    /// as white_ks, for black's queen side
+    pub black_qs: Option<Square>,
+    /// chess960, where castling is only read and written as king-takes-rook
+    pub chess960: bool,
+    /// the square behind a pawn that just moved two squares, where it can be taken en passant
+    pub enpassant_square: Option<Square>,
+    /// plies since the last capture or pawn move, for the fifty move rule
+    pub halfmoves: u16,
+    /// starts at 1 and goes up after every black move
     pub fullmoves: u16,
-    pub bkingpos: u8,
-    pub wkingpos: u8,
+    /// indexed by color
+    pub king_squares: [Square; 2],
 
-    pub precomputed_move_data: [MoveData; 64],
-    pub zobrist_table: [[u64; 12]; 64],
+    pub(crate) precomputed_move_data: [MoveData; 64],
+    /// the zobrist hash, kept up to date by every move
     pub hash: u64,
-    pub transposition_table: HashMap<u64, TranspositionEntry>,
-    pub white_ks_random: u64,
-    pub white_qs_random: u64,
-    pub black_ks_random: u64,
-    pub black_qs_random: u64,
-    pub wtomove_random: u64,
 }
 
+/// what make_null_move changed, for unmake_null_move to put back
+#[derive(Clone, Copy, Debug)]
+pub struct NullMoveUndo {
+    enpassant_square: Option<Square>,
+    halfmoves: u16,
+    hash: u64,
+}
+
+/// what stands on a square. an empty square has type Empty and color Black
 #[derive(Copy, Clone, Debug)]
 pub struct Piece {
+    /// the kind of piece
     pub piece_type: PieceTypes,
-    pub white: bool,
+    /// whose piece it is
+    pub color: Color,
 }
 
+/// what stands on an empty square
+pub const EMPTY_PIECE: Piece = Piece {
+    piece_type: PieceTypes::Empty,
+    color: Color::Black,
+};
+
 impl Move {
-    pub fn parse_from(m: &str) -> Move {
-        let mv = m.split_at(2);
-        let from = mv.0;
-        let mut to = "";
-        let mut promotion = "";
-        if m.len() == 4 {
-            to = mv.1;
-        } else if m.len() == 5 {
-            let mv2 = mv.1.split_at(2);
-            to = mv2.0;
-            promotion = mv2.1;
-        }
-        Move {
-            from: parse_square(from),
-            to: parse_square(to),
-            promotion: match promotion {
-                "n" => PieceTypes::Knight,
-                "b" => PieceTypes::Bishop,
-                "r" => PieceTypes::Rook,
-                "q" => PieceTypes::Queen,
-                _ => PieceTypes::Empty
-            },
+    /// no move, written 0000
+    pub const NULL: Move = Move(0);
+
+    /// a move from its squares and flags, one of the constants above
+    pub fn new(from: Square, to: Square, flags: u16) -> Move {
+        Move(from.index() as u16 | (to.index() as u16) << 6 | flags << 12)
+    }
+
+    /// the square the piece moves from
+    pub fn from(&self) -> Square {
+        Square::from_index((self.0 & 0x3f) as usize)
+    }
+
+    /// the square the piece moves to, the rook's square for castling
+    pub fn to(&self) -> Square {
+        Square::from_index((self.0 >> 6 & 0x3f) as usize)
+    }
+
+    /// one of the constants above
+    pub fn flags(&self) -> u16 {
+        self.0 >> 12
+    }
+
+    /// whether this is Move::NULL
+    pub fn is_null(&self) -> bool {
+        *self == Move::NULL
+    }
+
+    /// captures and capture-promotions, including en passant
+    pub fn is_capture(&self) -> bool {
+        self.flags() & CAPTURE_FLAG != 0
+    }
+
+    /// whether this is an en passant capture
+    pub fn is_ep(&self) -> bool {
+        self.flags() == EP_CAPTURE
+    }
+
+    /// castling to either side
+    pub fn is_castle(&self) -> bool {
+        self.flags() == KING_CASTLE || self.flags() == QUEEN_CASTLE
+    }
+
+    /// a pawn moving two squares
+    pub fn is_double_push(&self) -> bool {
+        self.flags() == DOUBLE_PUSH
+    }
+
+    /// promotions, with or without a capture
+    pub fn is_promotion(&self) -> bool {
+        self.flags() & PROMOTION_FLAG != 0
+    }
+
+    /// the piece a promotion promotes to, Empty for other moves
+    pub fn promotion(&self) -> PieceTypes {
+        match self.flags() {
+            KNIGHT_PROMOTION | KNIGHT_PROMOTION_CAPTURE => PieceTypes::Knight,
+            BISHOP_PROMOTION | BISHOP_PROMOTION_CAPTURE => PieceTypes::Bishop,
+            ROOK_PROMOTION | ROOK_PROMOTION_CAPTURE => PieceTypes::Rook,
+            QUEEN_PROMOTION | QUEEN_PROMOTION_CAPTURE => PieceTypes::Queen,
+            _ => PieceTypes::Empty,
+        }
+    }
+
+    /// builds the move from its squares, looking at the board for the flags.
+    /// castling is accepted as king-takes-rook, and outside of chess960 also as
+    /// the king moving two squares
+    pub fn from_squares(from: Square, to: Square, promotion: PieceTypes, board: &Board) -> Move {
+        let piece = board.board[from.index()];
+        let target = board.board[to.index()];
+        let capture = target.piece_type != PieceTypes::Empty && target.color != piece.color;
+
+        if piece.piece_type == PieceTypes::King {
+            for rook in board.castling_rooks(piece.color).into_iter().flatten() {
+                let (king_to, _) = castling_destinations(from, rook);
+                if to == rook || (!board.chess960 && to == king_to && from.distance(to) == 2) {
+                    let flags = if rook.file() > from.file() {
+                        KING_CASTLE
+                    } else {
+                        QUEEN_CASTLE
+                    };
+                    return Move::new(from, rook, flags);
+                }
+            }
         }
+
+        let flags = if promotion != PieceTypes::Empty {
+            promotion_flags(promotion, capture)
+        } else if capture {
+            CAPTURE
+        } else if piece.piece_type == PieceTypes::Pawn && Some(to) == board.enpassant_square {
+            EP_CAPTURE
+        } else if piece.piece_type == PieceTypes::Pawn && from.rank().distance(to.rank()) == 2 {
+            DOUBLE_PUSH
+        } else {
+            QUIET
+        };
+
+        Move::new(from, to, flags)
     }
 
+    /// long algebraic notation as used by uci, e.g. e2e4, e7e8q or 0000 for the null move
+    pub fn from_uci(m: &str, board: &Board) -> Result<Move, ParseMoveError> {
+        if m == "0000" {
+            return Ok(Move::NULL);
+        }
+        let (from, to, promotion) = parse_uci(m)?;
+
+        // a pawn reaching the last rank has to promote, and nothing else can
+        let piece = board.board[from.index()];
+        let promotes = piece.piece_type == PieceTypes::Pawn
+            && to.rank().relative_to(piece.color) == Rank::EIGHTH;
+        if promotes != (promotion != PieceTypes::Empty) {
+            return Err(ParseMoveError::Promotion(m.to_string()));
+        }
+
+        Ok(Move::from_squares(from, to, promotion, board))
+    }
+
+    /// the move in standard uci, castling as the king's two square move
     pub fn uci(&self) -> String {
-        format!("{}{}{}", SQUARES[self.from as usize], SQUARES[self.to as usize], match self.promotion {
+        self.to_uci(false)
+    }
+
+    /// castling is stored as king-takes-rook, which is also how chess960 writes it.
+    /// standard uci writes the square the king lands on instead
+    pub fn to_uci(self, chess960: bool) -> String {
+        if self.is_null() {
+            return String::from("0000");
+        }
+        let to = if self.is_castle() && !chess960 {
+            castling_destinations(self.from(), self.to()).0
+        } else {
+            self.to()
+        };
+        format!("{}{}{}", self.from(), to, match self.promotion() {
             PieceTypes::Bishop => "b",
             PieceTypes::Knight => "n",
             PieceTypes::Rook => "r",
@@ -112,70 +334,135 @@ impl Move {
 
 impl Debug for Move {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match write!(f, "{}{}{}", SQUARES[self.from as usize], SQUARES[self.to as usize], match self.promotion {
-            PieceTypes::Bishop => "b",
-            PieceTypes::Knight => "n",
-            PieceTypes::Rook => "r",
-            PieceTypes::Queen => "q",
-            _ => ""
-        }) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(std::fmt::Error),
-        }
+        write!(f, "{}", self.uci())
     }
 }
 
 impl Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut i: u8 = 0;
-        for piece in self.board {
-            match piece.piece_type {
-                PieceTypes::Empty => write!(f, "-"),
-                PieceTypes::Pawn => write!(f, "{}", if piece.white { "P" } else { "p" }),
-                PieceTypes::Bishop => write!(f, "{}", if piece.white { "B" } else { "b" }),
-                PieceTypes::Knight => write!(f, "{}", if piece.white { "N" } else { "n" }),
-                PieceTypes::Rook => write!(f, "{}", if piece.white { "R" } else { "r" }),
-                PieceTypes::Queen => write!(f, "{}", if piece.white { "Q" } else { "q" }),
-                PieceTypes::King => write!(f, "{}", if piece.white { "K" } else { "k" }),
-            }?;
-
-            if i % 8 == 7 {
-                println!();
+        write!(f, "{}", self.render(&RenderOptions::default()))
+    }
+}
+
+// Board::new panics on a malformed fen, so everything it relies on is checked first
+impl FromStr for Board {
+    type Err = ParseFenError;
+
+    fn from_str(fen: &str) -> Result<Board, ParseFenError> {
+        let error = || ParseFenError(fen.to_string());
+
+        let tokens: Vec<&str> = fen.split(' ').collect();
+        if tokens.len() != 6 {
+            return Err(error());
+        }
+
+        let ranks: Vec<&str> = tokens[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(error());
+        }
+        for rank in ranks {
+            let mut files = 0;
+            for c in rank.chars() {
+                files += match c {
+                    '1'..='8' => c as u32 - '0' as u32,
+                    'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => 1,
+                    _ => return Err(error()),
+                };
+            }
+            if files != 8 {
+                return Err(error());
             }
-            i += 1;
         }
-        Ok(())
+        if tokens[0].matches('K').count() != 1 || tokens[0].matches('k').count() != 1 {
+            return Err(error());
+        }
+
+        let side_to_move = tokens[1].parse::<Color>().map_err(|_| error())?;
+        // the square a pawn skipped over, so the sixth rank of the side that can take it
+        if tokens[3] != "-" {
+            let square = tokens[3].parse::<Square>().map_err(|_| error())?;
+            if square.rank().relative_to(side_to_move) != Rank::SIXTH {
+                return Err(error());
+            }
+        }
+        tokens[4].parse::<u16>().map_err(|_| error())?;
+        tokens[5].parse::<u16>().map_err(|_| error())?;
+
+        Ok(Board::new(fen))
+    }
+}
+
+/// written as its uci string, with castling always as king-takes-rook (e1h1 rather than
+/// e1g1) since a move does not know whether its game is chess960, and that form means the
+/// same in both. the string alone does not say whether a move is a capture, castle, en
+/// passant or double push, so moves are read back with MoveSeed
+#[cfg(feature = "serde")]
+impl serde::Serialize for Move {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_uci(true))
+    }
+}
+
+/// reads a move back from its uci string with Move::from_uci and the board it is played on
+#[cfg(feature = "serde")]
+#[derive(Clone, Copy)]
+pub struct MoveSeed<'a>(pub &'a Board);
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::DeserializeSeed<'de> for MoveSeed<'_> {
+    type Value = Move;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Move, D::Error> {
+        let uci = <String as serde::Deserialize>::deserialize(deserializer)?;
+        Move::from_uci(&uci, self.0).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Board {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.fen())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Board {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        fen.parse().map_err(serde::de::Error::custom)
     }
 }
 
 impl Board {
+    /// parses a fen, X-FEN or Shredder-FEN. panics on malformed input
     pub fn new(fen: &str) -> Self {
         let fen_array = fen.split(" ");
         let mut tokens: [&str; 6] = [""; 6];
 
-        let mut i = 0;
-
-        for token in fen_array {
+        for (i, token) in fen_array.enumerate() {
             tokens[i] = token;
-            i += 1;
         }
 
         let board_array = tokens[0].split("/");
 
         let mut board: [Piece; 64] = [Piece {
             piece_type: PieceTypes::Empty,
-            white: false,
+            color: Color::Black,
         }; 64];
-        let mut white_ks: bool = false;
-        let mut white_qs: bool = false;
-        let mut black_ks: bool = false;
-        let mut black_qs: bool = false;
-        let wtomove: bool = tokens[1] == "w";
+        let mut white_ks: Option<Square> = None;
+        let mut white_qs: Option<Square> = None;
+        let mut black_ks: Option<Square> = None;
+        let mut black_qs: Option<Square> = None;
+        let side_to_move = if tokens[1] == "w" {
+            Color::White
+        } else {
+            Color::Black
+        };
 
         let mut curr_square: usize = 0;
 
-        let mut bkingpos: u8 = 64;
-        let mut wkingpos: u8 = 64;
+        let mut bkingpos: Option<Square> = None;
+        let mut wkingpos: Option<Square> = None;
 
         for row in board_array {
             for square in row.chars() {
@@ -199,64 +486,64 @@ impl Board {
                     if square == 'p' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Pawn,
-                            white: false,
+                            color: Color::Black,
                         };
                     } else if square == 'b' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Bishop,
-                            white: false,
+                            color: Color::Black,
                         };
                     } else if square == 'n' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Knight,
-                            white: false,
+                            color: Color::Black,
                         };
                     } else if square == 'r' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Rook,
-                            white: false,
+                            color: Color::Black,
                         };
                     } else if square == 'q' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Queen,
-                            white: false,
+                            color: Color::Black,
                         };
                     } else if square == 'k' {
-                        bkingpos = curr_square as u8;
+                        bkingpos = Some(Square::from_index(curr_square));
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::King,
-                            white: false,
+                            color: Color::Black,
                         };
                     } else if square == 'P' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Pawn,
-                            white: true,
+                            color: Color::White,
                         };
                     } else if square == 'B' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Bishop,
-                            white: true,
+                            color: Color::White,
                         };
                     } else if square == 'N' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Knight,
-                            white: true,
+                            color: Color::White,
                         };
                     } else if square == 'R' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Rook,
-                            white: true,
+                            color: Color::White,
                         };
                     } else if square == 'Q' {
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::Queen,
-                            white: true,
+                            color: Color::White,
                         };
                     } else if square == 'K' {
-                        wkingpos = curr_square as u8;
+                        wkingpos = Some(Square::from_index(curr_square));
                         board[curr_square] = Piece {
                             piece_type: PieceTypes::King,
-                            white: true,
+                            color: Color::White,
                         };
                     } else {
                         panic!("invalid fen: {}", square);
@@ -266,367 +553,849 @@ impl Board {
             }
         }
 
-        if tokens[2].contains('K') {
-            white_ks = true;
+        let king_squares = [
+            wkingpos.expect("invalid fen: no white king"),
+            bkingpos.expect("invalid fen: no black king"),
+        ];
+
+        // KQkq mean the outermost rook, a file letter (Shredder-FEN and X-FEN) names the rook
+        for c in tokens[2].chars() {
+            let color = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let king = king_squares[color.index()];
+            if let Some(rook) = castling_rook(&board, king, c) {
+                match (color, rook.file() > king.file()) {
+                    (Color::White, true) => white_ks = Some(rook),
+                    (Color::White, false) => white_qs = Some(rook),
+                    (Color::Black, true) => black_ks = Some(rook),
+                    (Color::Black, false) => black_qs = Some(rook),
+                }
+            }
+        }
+
+        let enpassant_square = tokens[3].parse::<Square>().ok();
+
+        let halfmoves = tokens[4].parse().unwrap();
+        let fullmoves = tokens[5].parse().unwrap();
+
+        let mut ret_board = Board {
+            board,
+            side_to_move,
+            white_ks,
+            white_qs,
+            black_ks,
+            black_qs,
+            chess960: false,
+            enpassant_square,
+            halfmoves,
+            fullmoves,
+            precomputed_move_data: compute_distances(),
+            king_squares,
+            hash: 0u64,
+        };
+        ret_board.hash = generate_hash(&ret_board);
+        ret_board
+    }
+
+    /// the king side rook first, then the queen side one
+    pub fn castling_rooks(&self, color: Color) -> [Option<Square>; 2] {
+        if color.is_white() {
+            [self.white_ks, self.white_qs]
+        } else {
+            [self.black_ks, self.black_qs]
         }
+    }
+
+    /// X-FEN, which only names the rook by its file when it is not the outermost one
+    pub fn fen(&self) -> String {
+        self.write_fen(false)
+    }
 
-        if tokens[2].contains('Q') {
-            white_qs = true;
+    /// Shredder-FEN, which always names the castling rooks by their files
+    pub fn shredder_fen(&self) -> String {
+        self.write_fen(true)
+    }
+
+    fn write_fen(&self, shredder: bool) -> String {
+        let mut fen = String::new();
+        for rank in 0..8 {
+            let mut empty = 0;
+            for square in rank * 8..rank * 8 + 8 {
+                let piece = self.board[square];
+                if piece.piece_type == PieceTypes::Empty {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                let c = piece_char(piece.piece_type);
+                fen.push(if piece.color.is_white() { c } else { c.to_ascii_lowercase() });
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank < 7 {
+                fen.push('/');
+            }
         }
 
-        if tokens[2].contains('k') {
-            black_ks = true;
+        fen.push_str(&format!(" {} ", self.side_to_move));
+        fen.push_str(&self.castling_field(shredder));
+
+        match self.enpassant_square {
+            Some(square) => fen.push_str(&format!(" {}", square)),
+            None => fen.push_str(" -"),
         }
+        fen.push_str(&format!(" {} {}", self.halfmoves, self.fullmoves));
+        fen
+    }
 
-        if tokens[2].contains('q') {
-            black_qs = true;
+    // the castling rights as a fen writes them, KQkq or the rooks' files, - for none
+    pub(crate) fn castling_field(&self, shredder: bool) -> String {
+        let mut castling = String::new();
+        for color in Color::ALL {
+            let king = self.king_square(color);
+            for (rook, side) in self.castling_rooks(color).into_iter().zip(['k', 'q']) {
+                let Some(rook) = rook else { continue };
+                let file = rook.file().to_string();
+                let (side, file) = if color.is_white() {
+                    (side.to_ascii_uppercase(), file.to_ascii_uppercase())
+                } else {
+                    (side, file)
+                };
+                if !shredder && castling_rook(&self.board, king, side) == Some(rook) {
+                    castling.push(side);
+                } else {
+                    castling.push_str(&file);
+                }
+            }
         }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        castling
+    }
 
-        let enpassant_square;
+    /// the same position with the colours swapped and the board turned around,
+    /// so a white king on e1 becomes a black king on e8
+    pub fn mirror(&self) -> Board {
+        self.transformed(Square::flip, true)
+    }
 
-        if tokens[3] == "-" {
-            enpassant_square = 64;
-        } else {
-            enpassant_square = parse_square(tokens[3]);
+    /// the position mirrored between the king and queen side, which is only the
+    /// same game when neither side can castle
+    pub fn flip_horizontal(&self) -> Option<Board> {
+        if [self.white_ks, self.white_qs, self.black_ks, self.black_qs]
+            .iter()
+            .any(Option::is_some)
+        {
+            return None;
         }
+        Some(self.transformed(Square::flip_horizontal, false))
+    }
 
-        let fullmoves = tokens[5].parse().unwrap();
+    fn transformed(&self, map: fn(Square) -> Square, swap_colors: bool) -> Board {
+        let mut squares = [EMPTY_PIECE; 64];
+        for square in Square::all() {
+            let mut piece = self.board[square.index()];
+            if swap_colors {
+                piece.color = !piece.color;
+            }
+            squares[map(square).index()] = piece;
+        }
 
-        let mut ret_board = Board {
-            board: board,
-            wtomove: wtomove,
-            white_ks: white_ks,
-            white_qs: white_qs,
-            black_ks: black_ks,
-            black_qs: black_qs,
-            enpassant_square: enpassant_square,
-            fullmoves: fullmoves,
-            precomputed_move_data: compute_distances(),
-            bkingpos: bkingpos,
-            wkingpos: wkingpos,
-            zobrist_table: init_table(),
-            hash: 0u64,
-            transposition_table: HashMap::new(),
-            white_ks_random: random(),
-            white_qs_random: random(),
-            black_ks_random: random(),
-            black_qs_random: random(),
-            wtomove_random: random(),
+        let (white, black) = if swap_colors {
+            (Color::Black, Color::White)
+        } else {
+            (Color::White, Color::Black)
         };
-        ret_board.hash = generate_hash(&ret_board);
-        ret_board
+        let [white_ks, white_qs] = self.castling_rooks(white).map(|rook| rook.map(map));
+        let [black_ks, black_qs] = self.castling_rooks(black).map(|rook| rook.map(map));
+
+        let mut board = Board {
+            board: squares,
+            side_to_move: if swap_colors {
+                !self.side_to_move
+            } else {
+                self.side_to_move
+            },
+            white_ks,
+            white_qs,
+            black_ks,
+            black_qs,
+            chess960: self.chess960,
+            enpassant_square: self.enpassant_square.map(map),
+            halfmoves: self.halfmoves,
+            fullmoves: self.fullmoves,
+            king_squares: [map(self.king_square(white)), map(self.king_square(black))],
+            precomputed_move_data: self.precomputed_move_data,
+            hash: 0,
+        };
+        board.hash = generate_hash(&board);
+        board
     }
 
-    pub fn push(&mut self, m: &Move) -> impl Fn(&mut Board){
-        // assert_ne!(self.board[m.from as usize].piece_type, PieceTypes::Empty);
+    /// where the king of `color` is
+    pub fn king_square(&self, color: Color) -> Square {
+        self.king_squares[color.index()]
+    }
 
+    /// makes a legal move without checking it and returns the closure that undoes it
+    pub fn push(&mut self, m: &Move) -> impl Fn(&mut Board){
         let uboard = self.board;
-        let uwtomove = self.wtomove;
+        let uside_to_move = self.side_to_move;
         let uwhite_ks = self.white_ks;
         let uwhite_qs = self.white_qs;
         let ublack_ks = self.black_ks;
         let ublack_qs = self.black_qs;
         let uenpassant_square = self.enpassant_square;
+        let uhalfmoves = self.halfmoves;
         let ufullmoves = self.fullmoves;
-        let uprecomputed_move_data = self.precomputed_move_data;
-        let ubkingpos = self.bkingpos;
-        let uwkingpos = self.wkingpos;
+        let uking_squares = self.king_squares;
         let uhash = self.hash;
 
-        let temp: Piece;
-        match self.board[m.from as usize].piece_type {
-            PieceTypes::Empty => (),
-            PieceTypes::Pawn => if self.board[m.from as usize].white {
-                self.hash ^= self.zobrist_table[m.from as usize][WPAWN];
-            } else {
-                self.hash ^= self.zobrist_table[m.from as usize][BPAWN];
-            },
-            PieceTypes::Bishop => if self.board[m.from as usize].white {
-                self.hash ^= self.zobrist_table[m.from as usize][WBISHOP];
-            } else {
-                self.hash ^= self.zobrist_table[m.from as usize][BBISHOP];
-            },
-            PieceTypes::Knight => if self.board[m.from as usize].white {
-                self.hash ^= self.zobrist_table[m.from as usize][WKNIGHT];
-            } else {
-                self.hash ^= self.zobrist_table[m.from as usize][BKNIGHT];
-            },
-            PieceTypes::Rook => if self.board[m.from as usize].white {
-                self.hash ^= self.zobrist_table[m.from as usize][WROOK];
-            } else {
-                self.hash ^= self.zobrist_table[m.from as usize][BROOK];
-            },
-            PieceTypes::Queen => if self.board[m.from as usize].white {
-                self.hash ^= self.zobrist_table[m.from as usize][WQUEEN];
-            } else {
-                self.hash ^= self.zobrist_table[m.from as usize][BQUEEN];
-            },
-            PieceTypes::King => if self.board[m.from as usize].white {
-                self.hash ^= self.zobrist_table[m.from as usize][WKING];
-            } else {
-                self.hash ^= self.zobrist_table[m.to as usize][BKING];
-            },
+        let from = m.from();
+        let to = m.to();
+        let moving = self.board[from.index()];
+
+        if m.is_ep() {
+            // the captured pawn is just behind the square the capturing one lands on
+            let captured_square = to.backward(moving.color).unwrap().index();
+            self.hash ^= ZOBRIST.pieces[captured_square][piece_index(&self.board[captured_square])];
+            self.board[captured_square] = EMPTY_PIECE;
+        } else if m.is_capture() {
+            self.hash ^= ZOBRIST.pieces[to.index()][piece_index(&self.board[to.index()])];
         }
-        if m.promotion == PieceTypes::Empty {
-            match self.board[m.from as usize].piece_type {
-                PieceTypes::Empty => (),
-                PieceTypes::Pawn => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WPAWN];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BPAWN];
-                },
-                PieceTypes::Bishop => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WBISHOP];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BBISHOP];
-                },
-                PieceTypes::Knight => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WKNIGHT];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BKNIGHT];
-                },
-                PieceTypes::Rook => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WROOK];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BROOK];
-                },
-                PieceTypes::Queen => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WQUEEN];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BQUEEN];
-                },
-                PieceTypes::King => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WKING];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BKING];
-                },
-            }
-            temp = self.board[m.from as usize];
+
+        let king_to = if m.is_castle() {
+            let (king_to, rook_to) = castling_destinations(from, to);
+            let rook = self.board[to.index()];
+            self.hash ^= ZOBRIST.pieces[from.index()][piece_index(&moving)];
+            self.hash ^= ZOBRIST.pieces[to.index()][piece_index(&rook)];
+            self.hash ^= ZOBRIST.pieces[king_to.index()][piece_index(&moving)];
+            self.hash ^= ZOBRIST.pieces[rook_to.index()][piece_index(&rook)];
+            // in chess960 either piece may land on the other's start square
+            self.board[from.index()] = EMPTY_PIECE;
+            self.board[to.index()] = EMPTY_PIECE;
+            self.board[king_to.index()] = moving;
+            self.board[rook_to.index()] = rook;
+            king_to
         } else {
-            match m.promotion {
-                PieceTypes::Bishop => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WBISHOP];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BBISHOP];
-                },
-                PieceTypes::Knight => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WKNIGHT];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BKNIGHT];
-                },
-                PieceTypes::Rook => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WROOK];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BROOK];
-                },
-                PieceTypes::Queen => if self.board[m.from as usize].white {
-                    self.hash ^= self.zobrist_table[m.to as usize][WQUEEN];
-                } else {
-                    self.hash ^= self.zobrist_table[m.to as usize][BQUEEN];
-                },
-                _ => (),
-            }
-            temp = Piece {
-                piece_type: m.promotion,
-                white: self.board[m.from as usize].white,
-            }
-        }    
-
-        if self.board[m.from as usize].piece_type == PieceTypes::King {
-            if self.wtomove {
-                self.white_ks = false;
-                self.white_qs = false;
-                self.hash ^= self.white_ks_random;
-                self.hash ^= self.white_qs_random;
+            let placed = if m.is_promotion() {
+                Piece {
+                    piece_type: m.promotion(),
+                    color: moving.color,
+                }
             } else {
-                self.black_ks = false;
-                self.black_qs = false;
-                self.hash ^= self.black_ks_random;
-                self.hash ^= self.black_qs_random;
-            }
-        }
+                moving
+            };
+            self.hash ^= ZOBRIST.pieces[from.index()][piece_index(&moving)];
+            self.hash ^= ZOBRIST.pieces[to.index()][piece_index(&placed)];
+            self.board[from.index()] = EMPTY_PIECE;
+            self.board[to.index()] = placed;
+            to
+        };
 
+        if moving.piece_type == PieceTypes::King {
+            self.king_squares[moving.color.index()] = king_to;
+        }
 
-        if m.to == 63 {
-            self.white_ks = false;
-            self.hash ^= self.white_ks_random;
-        } else if m.to == 56 {
-            self.white_qs = false;
-            self.hash ^= self.white_qs_random;
-        } else if m.to == 0 {
-            self.black_qs = false;
-            self.hash ^= self.black_qs_random;
-        } else if m.to == 7 {
-            self.black_ks = false;
-            self.hash ^= self.black_ks_random;
-        }
-
-        if m.from == 63 {
-            self.white_ks = false;
-            self.hash ^= self.white_ks_random;
-        } else if m.from == 56 {
-            self.white_qs = false;
-            self.hash ^= self.white_qs_random;
-        } else if m.from == 0 {
-            self.black_qs = false;
-            self.hash ^= self.black_qs_random;
-        } else if m.from == 7 {
-            self.black_ks = false;
-            self.hash ^= self.black_ks_random;
-        }
-
-        if self.board[m.from as usize].piece_type == PieceTypes::Pawn {   
-            if m.to == self.enpassant_square {
-                if self.wtomove {
-                    self.board[(m.to + 8) as usize] = Piece {
-                        piece_type: PieceTypes::Empty,
-                        white: false,
-                    };
-                    self.hash ^= self.zobrist_table[m.to as usize + 8][BPAWN];
-                } else {
-                    self.board[(m.to - 8) as usize] = Piece {
-                        piece_type: PieceTypes::Empty,
-                        white: false,
-                    };
-                    self.hash ^= self.zobrist_table[m.to as usize + 8][WPAWN];
+        // a right is lost once the king or that rook moves, or the rook is captured
+        let king_moved = moving.piece_type == PieceTypes::King;
+        for (right, key, color) in [
+            (&mut self.white_ks, ZOBRIST.castling[0], Color::White),
+            (&mut self.white_qs, ZOBRIST.castling[1], Color::White),
+            (&mut self.black_ks, ZOBRIST.castling[2], Color::Black),
+            (&mut self.black_qs, ZOBRIST.castling[3], Color::Black),
+        ] {
+            if let Some(rook) = *right {
+                if (king_moved && moving.color == color) || from == rook || to == rook {
+                    *right = None;
+                    self.hash ^= key;
                 }
             }
-            
-            if self.wtomove && m.from - m.to == 16 {
-                self.enpassant_square = m.from - 8;
-            } else if !self.wtomove && m.to - m.from == 16 {
-                self.enpassant_square = m.to - 8;
-            } else {
-                self.enpassant_square = 64;
-            }
-        } else {
-            self.enpassant_square = 64;
         }
 
-        if self.board[m.from as usize].piece_type == PieceTypes::King {
-            if self.board[m.from as usize].white {
-                self.wkingpos = m.to as u8;
-            } else {
-                self.bkingpos = m.to as u8;
-            }
+        if let Some(square) = self.enpassant_square {
+            self.hash ^= ZOBRIST.enpassant[square.file().index()];
         }
+        self.enpassant_square = if m.is_double_push() {
+            self.hash ^= ZOBRIST.enpassant[from.file().index()];
+            from.forward(moving.color)
+        } else {
+            None
+        };
 
-        if self.board[m.from as usize].piece_type == PieceTypes::King
-            && m.from == 60
-            && self.board[m.from as usize].white
-        {
-            if m.to == 62 {
-                self.board[63] = Piece {
-                    piece_type: PieceTypes::Empty,
-                    white: true,
-                };
-                self.board[61] = Piece {
-                    piece_type: PieceTypes::Rook,
-                    white: true,
-                };
-                self.hash ^= self.zobrist_table[63][WROOK];
-                self.hash ^= self.zobrist_table[61][WROOK];
-            } else if m.to == 58 {
-                self.board[56] = Piece {
-                    piece_type: PieceTypes::Empty,
-                    white: true,
-                };
-                self.board[59] = Piece {
-                    piece_type: PieceTypes::Rook,
-                    white: true,
-                };
-                self.hash ^= self.zobrist_table[56][WROOK];
-                self.hash ^= self.zobrist_table[59][WROOK];
-            }
-        } else if self.board[m.from as usize].piece_type == PieceTypes::King
-            && m.from == 4
-            && !self.board[m.from as usize].white
-        {
-            if m.to == 6 {
-                self.board[7] = Piece {
-                    piece_type: PieceTypes::Empty,
-                    white: false,
-                };
-                self.board[5] = Piece {
-                    piece_type: PieceTypes::Rook,
-                    white: false,
-                };
-                self.hash ^= self.zobrist_table[7][BROOK];
-                self.hash ^= self.zobrist_table[5][BROOK];
-            } else if m.to == 2 {
-                self.board[0] = Piece {
-                    piece_type: PieceTypes::Empty,
-                    white: false,
-                };
-                self.board[3] = Piece {
-                    piece_type: PieceTypes::Rook,
-                    white: false,
-                };
-                self.hash ^= self.zobrist_table[0][BROOK];
-                self.hash ^= self.zobrist_table[3][BROOK];
-            }
+        if moving.piece_type == PieceTypes::Pawn || m.is_capture() {
+            self.halfmoves = 0;
+        } else {
+            self.halfmoves += 1;
         }
 
-        self.board[m.to as usize] = temp;
-        self.board[m.from as usize] = Piece {
-            piece_type: PieceTypes::Empty,
-            white: false,
-        };
-
-        if !self.wtomove {
+        if self.side_to_move == Color::Black {
             self.fullmoves += 1;
         }
 
-        self.wtomove = !self.wtomove;
-        self.hash ^= self.wtomove_random;
+        self.side_to_move = !self.side_to_move;
+        self.hash ^= ZOBRIST.side_to_move;
 
         move |board: &mut Board| {
             board.board = uboard;
-            board.wtomove = uwtomove;
+            board.side_to_move = uside_to_move;
             board.white_ks = uwhite_ks;
             board.white_qs = uwhite_qs;
             board.black_ks = ublack_ks;
             board.black_qs = ublack_qs;
             board.enpassant_square = uenpassant_square;
+            board.halfmoves = uhalfmoves;
             board.fullmoves = ufullmoves;
-            board.precomputed_move_data = uprecomputed_move_data;
-            board.bkingpos = ubkingpos;
-            board.wkingpos = uwkingpos;
+            board.king_squares = uking_squares;
             board.hash = uhash;
         }
     }
+
+    /// passes the turn without moving a piece
+    pub fn make_null_move(&mut self) -> NullMoveUndo {
+        let undo = NullMoveUndo {
+            enpassant_square: self.enpassant_square,
+            halfmoves: self.halfmoves,
+            hash: self.hash,
+        };
+
+        if let Some(square) = self.enpassant_square.take() {
+            self.hash ^= ZOBRIST.enpassant[square.file().index()];
+        }
+        self.halfmoves += 1;
+        self.side_to_move = !self.side_to_move;
+        self.hash ^= ZOBRIST.side_to_move;
+
+        undo
+    }
+
+    /// takes back a null move made with make_null_move
+    pub fn unmake_null_move(&mut self, undo: NullMoveUndo) {
+        self.side_to_move = !self.side_to_move;
+        self.enpassant_square = undo.enpassant_square;
+        self.halfmoves = undo.halfmoves;
+        self.hash = undo.hash;
+    }
+
+    /// whether the move could be generated here, ignoring whether it leaves the king in check
+    pub fn is_pseudo_legal(&self, m: &Move) -> bool {
+        if m.is_null() {
+            return false;
+        }
+        let mut moves = MoveList::new();
+        generate_piece_moves(self, m.from(), &mut moves);
+        moves.contains(m)
+    }
+
+    /// whether the move can be played here without leaving the king in check
+    pub fn is_legal(&self, m: &Move) -> bool {
+        if !self.is_pseudo_legal(m) {
+            return false;
+        }
+        let mut squares = self.board;
+        move_pieces(&mut squares, m);
+        let king = if m.is_castle() {
+            castling_destinations(m.from(), m.to()).0
+        } else if self.board[m.from().index()].piece_type == PieceTypes::King {
+            m.to()
+        } else {
+            self.king_square(self.side_to_move)
+        };
+        square_attackers(&squares, &self.precomputed_move_data, king, self.side_to_move) == 0
+    }
+
+    /// push for moves that come from outside the engine, e.g. typed in by a user
+    pub fn try_push(&mut self, m: &Move) -> Result<impl Fn(&mut Board), IllegalMoveError> {
+        if self.is_legal(m) {
+            Ok(self.push(m))
+        } else {
+            Err(IllegalMoveError(*m))
+        }
+    }
+
+    /// whether the move checks the opponent's king, without pushing it
+    pub fn gives_check(&self, m: &Move) -> bool {
+        if m.is_null() {
+            return false;
+        }
+        let mut squares = self.board;
+        move_pieces(&mut squares, m);
+        let them = !self.side_to_move;
+        square_attackers(&squares, &self.precomputed_move_data, self.king_square(them), them) != 0
+    }
+
+    /// standard algebraic notation of a legal move, e.g. Nbd7, exd6, O-O or e8=Q#
+    pub fn san(&mut self, m: &Move) -> String {
+        let piece = self.board[m.from().index()];
+        let mut san = String::new();
+
+        if m.is_castle() {
+            san.push_str(if m.flags() == KING_CASTLE { "O-O" } else { "O-O-O" });
+        } else {
+            if piece.piece_type == PieceTypes::Pawn {
+                if m.is_capture() {
+                    san.push_str(&m.from().file().to_string());
+                }
+            } else {
+                san.push(piece_char(piece.piece_type));
+
+                let mut same_file = false;
+                let mut same_rank = false;
+                let mut ambiguous = false;
+                for other in generate_legal_moves(self) {
+                    if other.to() == m.to()
+                        && other.from() != m.from()
+                        && self.board[other.from().index()].piece_type == piece.piece_type
+                    {
+                        ambiguous = true;
+                        same_file |= other.from().file() == m.from().file();
+                        same_rank |= other.from().rank() == m.from().rank();
+                    }
+                }
+                if ambiguous {
+                    if !same_file {
+                        san.push_str(&m.from().file().to_string());
+                    } else if !same_rank {
+                        san.push_str(&m.from().rank().to_string());
+                    } else {
+                        san.push_str(&m.from().to_string());
+                    }
+                }
+            }
+
+            if m.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&m.to().to_string());
+
+            if m.is_promotion() {
+                san.push('=');
+                san.push(piece_char(m.promotion()));
+            }
+        }
+
+        if self.gives_check(m) {
+            let undo = self.push(m);
+            let mate = generate_legal_moves(self).is_empty();
+            undo(self);
+            san.push(if mate { '#' } else { '+' });
+        }
+
+        san
+    }
 }
 
-pub fn parse_square(square: &str) -> u8 {
-    let rank_file = square.chars();
-    let mut asquare: Vec<char> = Vec::new();
+// the squares and promotion piece of a move in uci notation
+fn parse_uci(m: &str) -> Result<(Square, Square, PieceTypes), ParseMoveError> {
+    if !m.is_ascii() || (m.len() != 4 && m.len() != 5) {
+        return Err(ParseMoveError::Length(m.to_string()));
+    }
+
+    let from: Square = m[0..2].parse()?;
+    let to: Square = m[2..4].parse()?;
+    let promotion = match &m[4..] {
+        "" => PieceTypes::Empty,
+        "n" => PieceTypes::Knight,
+        "b" => PieceTypes::Bishop,
+        "r" => PieceTypes::Rook,
+        "q" => PieceTypes::Queen,
+        _ => return Err(ParseMoveError::Promotion(m.to_string())),
+    };
+    Ok((from, to, promotion))
+}
+
+fn promotion_flags(promotion: PieceTypes, capture: bool) -> u16 {
+    let base = if capture {
+        KNIGHT_PROMOTION_CAPTURE
+    } else {
+        KNIGHT_PROMOTION
+    };
+    base + match promotion {
+        PieceTypes::Bishop => 1,
+        PieceTypes::Rook => 2,
+        PieceTypes::Queen => 3,
+        _ => 0,
+    }
+}
+
+/// the letter of a piece as in san and white's pieces in a fen, - for Empty
+pub fn piece_char(piece_type: PieceTypes) -> char {
+    match piece_type {
+        PieceTypes::Pawn => 'P',
+        PieceTypes::Bishop => 'B',
+        PieceTypes::Knight => 'N',
+        PieceTypes::Rook => 'R',
+        PieceTypes::Queen => 'Q',
+        PieceTypes::King => 'K',
+        PieceTypes::Empty => '-',
+    }
+}
+
+// moves the pieces like push does, without touching anything else about the position
+fn move_pieces(squares: &mut [Piece; 64], m: &Move) {
+    let from = m.from();
+    let to = m.to();
+    let moving = squares[from.index()];
+
+    if m.is_ep() {
+        squares[to.backward(moving.color).unwrap().index()] = EMPTY_PIECE;
+    }
+
+    if m.is_castle() {
+        let (king_to, rook_to) = castling_destinations(from, to);
+        let rook = squares[to.index()];
+        squares[from.index()] = EMPTY_PIECE;
+        squares[to.index()] = EMPTY_PIECE;
+        squares[king_to.index()] = moving;
+        squares[rook_to.index()] = rook;
+        return;
+    }
+
+    squares[from.index()] = EMPTY_PIECE;
+    squares[to.index()] = if m.is_promotion() {
+        Piece {
+            piece_type: m.promotion(),
+            color: moving.color,
+        }
+    } else {
+        moving
+    };
+}
+
+/// where the king and the rook end up when castling, the g and f files
+/// on the king side and the c and d files on the queen side
+pub fn castling_destinations(king: Square, rook: Square) -> (Square, Square) {
+    let rank = king.rank();
+    if rook.file() > king.file() {
+        (Square::new(File::G, rank), Square::new(File::F, rank))
+    } else {
+        (Square::new(File::C, rank), Square::new(File::D, rank))
+    }
+}
+
+// the rook a castling character of a fen refers to
+fn castling_rook(board: &[Piece; 64], king: Square, c: char) -> Option<Square> {
+    let color = if c.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let back_rank = Rank::FIRST.relative_to(color);
+    if king.rank() != back_rank {
+        return None;
+    }
+    let is_rook = |square: &Square| {
+        let piece = board[square.index()];
+        piece.piece_type == PieceTypes::Rook && piece.color == color
+    };
+    let back_rank_squares = File::all().map(|file| Square::new(file, back_rank));
+
+    match c.to_ascii_lowercase() {
+        'k' => back_rank_squares
+            .filter(|square| square.file() > king.file())
+            .filter(is_rook)
+            .last(),
+        'q' => back_rank_squares
+            .filter(|square| square.file() < king.file())
+            .find(is_rook),
+        file @ 'a'..='h' => {
+            let file: File = file.to_string().parse().ok()?;
+            Some(Square::new(file, back_rank)).filter(is_rook)
+        }
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Board, Move, ParseMoveError, PieceTypes, CAPTURE, DOUBLE_PUSH, EP_CAPTURE, KING_CASTLE,
+        QUEEN_CASTLE, QUIET, STARTPOS,
+    };
+    use crate::game::movegen::generate_legal_moves;
+    use crate::game::square::{ParseSquareError, Square};
+
+    fn square(name: &str) -> Square {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn legality() {
+        let mut board = Board::new(STARTPOS);
+        let legal = |board: &Board, m: &Move| (board.is_pseudo_legal(m), board.is_legal(m));
+
+        // nothing on the square, the opponent's piece, the null move
+        assert_eq!(legal(&board, &Move::new(square("e3"), square("e4"), QUIET)), (false, false));
+        assert_eq!(legal(&board, &Move::new(square("e7"), square("e5"), DOUBLE_PUSH)), (false, false));
+        assert_eq!(legal(&board, &Move::NULL), (false, false));
+        // the right squares with the wrong flags
+        assert_eq!(legal(&board, &Move::new(square("e2"), square("e4"), QUIET)), (false, false));
+        assert_eq!(legal(&board, &Move::new(square("e2"), square("e4"), DOUBLE_PUSH)), (true, true));
 
-    for token in rank_file {
-        asquare.push(token);
+        // a tt move stored for a different position
+        let kiwipete = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let from_kiwipete = Move::from_uci("e2a6", &kiwipete).unwrap();
+        assert!(kiwipete.is_legal(&from_kiwipete));
+        assert_eq!(legal(&board, &from_kiwipete), (false, false));
+
+        // illegal moves are refused and leave the board as it was
+        assert!(board.try_push(&from_kiwipete).is_err());
+        assert_eq!(board.fen(), STARTPOS);
+        assert!(board.try_push(&Move::from_uci("g1f3", &board).unwrap()).is_ok());
+        assert_eq!(board.fen(), "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1");
+
+        // castling through an attacked square, but not on the other side
+        let mut board = Board::new("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        let king_side = Move::new(square("e1"), square("h1"), KING_CASTLE);
+        assert!(!board.is_legal(&king_side));
+        assert!(board.try_push(&king_side).is_err());
+        assert!(board.is_legal(&Move::new(square("e1"), square("a1"), QUEEN_CASTLE)));
+
+        // taking en passant takes both pawns off the fifth rank and opens it to the rook
+        let board = Board::new("8/8/8/KPp4r/8/8/8/7k w - c6 0 1");
+        let ep = Move::new(square("b5"), square("c6"), EP_CAPTURE);
+        assert_eq!(legal(&board, &ep), (true, false));
+
+        // a pinned piece cannot leave the line of the pin, but can move along it
+        let board = Board::new("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1");
+        assert_eq!(legal(&board, &Move::new(square("e2"), square("d2"), QUIET)), (true, false));
+        assert_eq!(legal(&board, &Move::new(square("e2"), square("e5"), QUIET)), (true, true));
+        assert_eq!(legal(&board, &Move::new(square("e2"), square("e7"), CAPTURE)), (true, true));
+    }
+
+    #[test]
+    fn checks() {
+        let gives_check = |fen: &str, m: &str| {
+            let board = Board::new(fen);
+            board.gives_check(&Move::from_uci(m, &board).unwrap())
+        };
+
+        // direct
+        assert!(gives_check("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"));
+        assert!(!gives_check("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a7"));
+        // discovered by the knight stepping off the file
+        assert!(gives_check("4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1", "e2c3"));
+        // both pawns leave the rank when taking en passant
+        assert!(gives_check("8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 1", "e5d6"));
+        assert!(!gives_check("8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 1", "e5e6"));
+        // by the rook after castling
+        assert!(gives_check("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1"));
+        // by the promoted piece, but not when it is a knight
+        assert!(gives_check("3k4/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"));
+        assert!(gives_check("3k4/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8r"));
+        assert!(!gives_check("3k4/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8n"));
+        assert!(!gives_check(STARTPOS, "0000"));
+    }
+
+    #[test]
+    fn san() {
+        let san = |fen: &str, m: &str| {
+            let mut board = Board::new(fen);
+            let m = Move::from_uci(m, &board).unwrap();
+            let san = board.san(&m);
+            assert_eq!(board.fen(), Board::new(fen).fen());
+            san
+        };
+
+        assert_eq!(san(STARTPOS, "e2e4"), "e4");
+        assert_eq!(san(STARTPOS, "g1f3"), "Nf3");
+        assert_eq!(
+            san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2", "e4d5"),
+            "exd5"
+        );
+
+        // by file, by rank, and by square when neither is enough
+        assert_eq!(san("1n2k3/8/5n2/8/8/8/8/4K3 b - - 0 1", "b8d7"), "Nbd7");
+        assert_eq!(san("1n2k3/8/5n2/8/8/8/8/4K3 b - - 0 1", "f6d7"), "Nfd7");
+        assert_eq!(san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a5a3"), "R5a3");
+        assert_eq!(san("8/8/1k6/8/4Q2Q/8/8/K6Q w - - 0 1", "h4e1"), "Qh4e1");
+
+        assert_eq!(san("3k4/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), "a8=Q+");
+        assert_eq!(san("3k4/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8n"), "a8=N");
+        assert_eq!(san("r3k3/8/8/8/8/8/8/4K3 b q - 0 1", "e8c8"), "O-O-O");
+        assert_eq!(san("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1"), "O-O+");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+        assert_eq!(san("6k1/5p1p/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8+");
     }
 
-    let rank: u8 = asquare[1].to_digit(10).unwrap() as u8;
-    let filechr: char = asquare[0];
+    #[test]
+    fn parse_errors() {
+        for s in ["", "e", "e9", "i2", "E2", "e22", "\u{e9}2"] {
+            assert_eq!(s.parse::<Square>(), Err(ParseSquareError(s.to_string())));
+        }
+        assert_eq!("h8".parse::<Square>().unwrap().to_string(), "h8");
 
-    let mut file: u8 = 0;
+        let board = Board::new("k7/4P3/8/8/8/8/4P3/4K3 w - - 0 1");
+        let parse = |m: &str| Move::from_uci(m, &board);
+        let length = |m: &str| Err(ParseMoveError::Length(m.to_string()));
+        let square = |s: &str| Err(ParseMoveError::Square(ParseSquareError(s.to_string())));
+        let promotion = |m: &str| Err(ParseMoveError::Promotion(m.to_string()));
 
-    if filechr == 'a' {
-        file = 0;
-    } else if filechr == 'b' {
-        file = 1;
-    } else if filechr == 'c' {
-        file = 2;
-    } else if filechr == 'd' {
-        file = 3;
-    } else if filechr == 'e' {
-        file = 4;
-    } else if filechr == 'f' {
-        file = 5;
-    } else if filechr == 'g' {
-        file = 6;
-    } else if filechr == 'h' {
-        file = 7;
+        assert_eq!(parse(""), length(""));
+        assert_eq!(parse("e2"), length("e2"));
+        assert_eq!(parse("e2e4qq"), length("e2e4qq"));
+        // four characters, but not four bytes
+        assert_eq!(parse("e2\u{e9}4"), length("e2\u{e9}4"));
+        assert_eq!(parse("e2e9"), square("e9"));
+        assert_eq!(parse("i2e4"), square("i2"));
+        // a promotion piece where there is no promotion, a bad one, and none where there is
+        assert_eq!(parse("e2e4q"), promotion("e2e4q"));
+        assert_eq!(parse("e7e8k"), promotion("e7e8k"));
+        assert_eq!(parse("e7e8"), promotion("e7e8"));
+        assert_eq!(parse("e7e8q").unwrap().promotion(), PieceTypes::Queen);
+        assert_eq!(parse("0000"), Ok(Move::NULL));
     }
 
-    8 * (8 - rank) + file
+    #[test]
+    fn move_encoding() {
+        for from in Square::all() {
+            for to in Square::all() {
+                for flags in 0..16 {
+                    let m = Move::new(from, to, flags);
+                    assert_eq!((m.from(), m.to(), m.flags()), (from, to, flags));
+                }
+            }
+        }
+        assert!(Move::NULL.is_null());
+        assert_eq!(Move::default(), Move::NULL);
+        assert_eq!(Move::NULL.to_uci(false), "0000");
+    }
+
+    #[test]
+    fn uci_round_trip() {
+        for fen in [
+            STARTPOS,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let mut board = Board::new(fen);
+            for m in generate_legal_moves(&mut board) {
+                assert_eq!(Move::from_uci(&m.to_uci(false), &board), Ok(m), "{}", fen);
+                assert_eq!(Move::from_uci(&m.to_uci(true), &board), Ok(m), "{}", fen);
+            }
+        }
+
+        let mut board = Board::new("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9");
+        board.chess960 = true;
+        for m in generate_legal_moves(&mut board) {
+            assert_eq!(Move::from_uci(&m.to_uci(true), &board), Ok(m));
+        }
+
+        // castling is written where the king lands in standard uci, king-takes-rook in chess960
+        let board = Board::new("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let castle = Move::from_uci("e1g1", &board).unwrap();
+        assert!(castle.is_castle());
+        assert_eq!((castle.to_uci(false), castle.to_uci(true)), ("e1g1".into(), "e1h1".into()));
+        assert_eq!(Move::from_uci("e1c1", &board).unwrap().to_uci(true), "e1a1");
+
+        let board = Board::new("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8");
+        let promotion = Move::from_uci("d7c8n", &board).unwrap();
+        assert!(promotion.is_capture() && promotion.is_promotion());
+        assert_eq!(promotion.promotion(), PieceTypes::Knight);
+        assert_eq!(promotion.to_uci(false), "d7c8n");
+    }
+
+    #[test]
+    fn mirror() {
+        let board = Board::new("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert_eq!(
+            board.mirror().fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1"
+        );
+        assert_eq!(board.mirror().mirror().fen(), board.fen());
+        assert_eq!(board.mirror().mirror().hash, board.hash);
+        assert!(Board::new(STARTPOS).flip_horizontal().is_none());
+
+        let board = Board::new("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1");
+        assert_eq!(
+            board.flip_horizontal().unwrap().fen(),
+            "8/5p2/4p3/r5PK/k1p3R1/8/1P1P4/8 w - - 0 1"
+        );
+    }
+
+    #[test]
+    fn display() {
+        let board = Board::new(STARTPOS);
+        let lines: Vec<String> = board.to_string().lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0].trim_end(), "8  r  n  b  q  k  b  n  r");
+        assert_eq!(lines[7].trim_end(), "1  R  N  B  Q  K  B  N  R");
+        assert_eq!(lines[8].trim_end(), "   a  b  c  d  e  f  g  h");
+        assert_eq!(lines[9], "white to move, castling KQkq, en passant -, halfmoves 0, move 1");
+    }
+
+    #[test]
+    fn parse_fen() {
+        let board: Board = STARTPOS.parse().unwrap();
+        assert_eq!(board.fen(), STARTPOS);
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert_eq!(fen.parse::<Board>().unwrap().fen(), fen);
+
+        for fen in [
+            "",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e4 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e6 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1",
+        ] {
+            assert!(fen.parse::<Board>().is_err(), "{}", fen);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use super::{Color, Move, MoveSeed, PieceTypes};
+        use crate::game::movegen::generate_legal_moves;
+        use serde::de::DeserializeSeed;
+
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::new(fen);
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, format!("{:?}", fen));
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap().fen(), fen);
+        assert!(serde_json::from_str::<Board>("\"8/8/8/8 w - - 0 1\"").is_err());
+
+        let read = |json: &str, board: &Board| {
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            MoveSeed(board).deserialize(&mut deserializer)
+        };
+        for m in generate_legal_moves(&mut board) {
+            let json = serde_json::to_string(&m).unwrap();
+            assert_eq!(read(&json, &board).unwrap(), m);
+        }
+        let castle = Move::from_squares(
+            board.king_square(Color::White),
+            board.white_ks.unwrap(),
+            PieceTypes::Empty,
+            &board,
+        );
+        assert_eq!(serde_json::to_string(&castle).unwrap(), "\"e1h1\"");
+        assert_eq!(read("\"e1h1\"", &board).unwrap(), castle);
+        assert_eq!(serde_json::to_string(&Move::NULL).unwrap(), "\"0000\"");
+        assert_eq!(read("\"0000\"", &board).unwrap(), Move::NULL);
+        assert!(read("\"e1h\"", &board).is_err());
+        assert!(read("7", &board).is_err());
+
+        assert_eq!(serde_json::to_string(&PieceTypes::Queen).unwrap(), "\"Queen\"");
+    }
 }
@@ -0,0 +1,69 @@
+//! The two sides.
+
+use std::{fmt::Display, ops::Not, str::FromStr};
+
+/// the side a piece belongs to, or the side to move
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Color {
+    /// the side that moves first
+    White = 0,
+    /// the other side
+    Black = 1,
+}
+
+/// why a string is not w or b
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseColorError(pub String);
+
+impl Display for ParseColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid color: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+impl Color {
+    /// both sides, white first
+    pub const ALL: [Color; 2] = [Color::White, Color::Black];
+
+    /// 0 for white and 1 for black, for indexing tables
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// whether this is Color::White
+    pub fn is_white(self) -> bool {
+        self == Color::White
+    }
+}
+
+impl Not for Color {
+    type Output = Color;
+
+    fn not(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+// w and b, the way the side to move is written in a fen
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Color, ParseColorError> {
+        match s {
+            "w" => Ok(Color::White),
+            "b" => Ok(Color::Black),
+            _ => Err(ParseColorError(s.to_string())),
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", if self.is_white() { "w" } else { "b" })
+    }
+}
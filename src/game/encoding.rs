@@ -0,0 +1,277 @@
+//! A fixed-size binary encoding of a position, for storing large numbers of them.
+
+use std::fmt::Display;
+
+use crate::engine::hash::generate_hash;
+
+use super::board::{Board, Piece, PieceTypes, EMPTY_PIECE};
+use super::color::Color;
+use super::movegen::compute_distances;
+use super::square::{Rank, Square};
+
+/// bytes 0-7: occupancy bitboard, little endian, bit n set when square index n has a piece
+/// bytes 8-23: a nibble per piece in the order of the occupancy bits, low nibble first
+/// byte 24: bit 0 set when black is to move, bit 1 for chess960
+/// byte 25: the en passant square's index, or 64 when there is none
+/// bytes 26-27 and 28-29: halfmoves and fullmoves, little endian
+pub const ENCODED_SIZE: usize = 30;
+
+/// a position in ENCODED_SIZE bytes, as written by Board::encode
+pub type EncodedBoard = [u8; ENCODED_SIZE];
+
+// the low three bits of a nibble are the piece type as in PieceTypes, the high bit is set for
+// black. a rook that can still castle is stored as this type instead, which needs no extra
+// space and works for chess960 rooks on any file
+const CASTLING_ROOK: u8 = 7;
+const BLACK_BIT: u8 = 0b1000;
+
+const BLACK_TO_MOVE: u8 = 0b01;
+const CHESS960: u8 = 0b10;
+const NO_ENPASSANT: u8 = 64;
+
+/// why a byte string is not an encoded position
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeError {
+    /// more than 32 squares are occupied
+    Occupancy(u64),
+    /// a nibble that is not a piece
+    Piece(u8),
+    /// a side without exactly one king
+    Kings,
+    /// a castling rook off the king's first rank, or a second one on the same side
+    Castling(Square),
+    /// an en passant square that is neither a square nor on the right rank
+    EnPassant(u8),
+    /// unknown bits in the flag byte
+    Flags(u8),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Occupancy(occupancy) => {
+                write!(f, "{} pieces do not fit", occupancy.count_ones())
+            }
+            DecodeError::Piece(nibble) => write!(f, "invalid piece nibble {}", nibble),
+            DecodeError::Kings => write!(f, "expected one king of each colour"),
+            DecodeError::Castling(square) => write!(f, "invalid castling rook on {}", square),
+            DecodeError::EnPassant(index) => write!(f, "invalid en passant square {}", index),
+            DecodeError::Flags(flags) => write!(f, "invalid flags {:#04b}", flags),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Board {
+    /// None for positions with more than 32 pieces, which do not fit
+    pub fn encode(&self) -> Option<EncodedBoard> {
+        let mut bytes = [0; ENCODED_SIZE];
+        let castling_rooks = [Color::White, Color::Black].map(|color| self.castling_rooks(color));
+
+        let mut occupancy: u64 = 0;
+        let mut count = 0;
+        for square in Square::all() {
+            let piece = self.board[square.index()];
+            if piece.piece_type == PieceTypes::Empty {
+                continue;
+            }
+            if count == 32 {
+                return None;
+            }
+            occupancy |= square.bit();
+
+            let mut nibble = if castling_rooks[piece.color.index()].contains(&Some(square)) {
+                CASTLING_ROOK
+            } else {
+                piece.piece_type as u8
+            };
+            if !piece.color.is_white() {
+                nibble |= BLACK_BIT;
+            }
+            bytes[8 + count / 2] |= nibble << (4 * (count % 2));
+            count += 1;
+        }
+        bytes[0..8].copy_from_slice(&occupancy.to_le_bytes());
+
+        if !self.side_to_move.is_white() {
+            bytes[24] |= BLACK_TO_MOVE;
+        }
+        if self.chess960 {
+            bytes[24] |= CHESS960;
+        }
+        bytes[25] = self
+            .enpassant_square
+            .map_or(NO_ENPASSANT, |square| square.index() as u8);
+        bytes[26..28].copy_from_slice(&self.halfmoves.to_le_bytes());
+        bytes[28..30].copy_from_slice(&self.fullmoves.to_le_bytes());
+
+        Some(bytes)
+    }
+
+    /// reads back a position written by encode
+    pub fn decode(bytes: &EncodedBoard) -> Result<Board, DecodeError> {
+        let occupancy = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        if occupancy.count_ones() > 32 {
+            return Err(DecodeError::Occupancy(occupancy));
+        }
+
+        let mut squares = [EMPTY_PIECE; 64];
+        let mut castling = Vec::new();
+        let mut kings: [Option<Square>; 2] = [None; 2];
+        for (count, square) in Square::all()
+            .filter(|square| occupancy & square.bit() != 0)
+            .enumerate()
+        {
+            let nibble = bytes[8 + count / 2] >> (4 * (count % 2)) & 0xf;
+            let color = if nibble & BLACK_BIT == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let piece_type = match nibble & !BLACK_BIT {
+                1 => PieceTypes::Pawn,
+                2 => PieceTypes::Bishop,
+                3 => PieceTypes::Knight,
+                4 => PieceTypes::Rook,
+                5 => PieceTypes::Queen,
+                6 => PieceTypes::King,
+                CASTLING_ROOK => {
+                    castling.push((square, color));
+                    PieceTypes::Rook
+                }
+                _ => return Err(DecodeError::Piece(nibble)),
+            };
+            if piece_type == PieceTypes::King {
+                if kings[color.index()].is_some() {
+                    return Err(DecodeError::Kings);
+                }
+                kings[color.index()] = Some(square);
+            }
+            squares[square.index()] = Piece { piece_type, color };
+        }
+        let (Some(white_king), Some(black_king)) = (kings[0], kings[1]) else {
+            return Err(DecodeError::Kings);
+        };
+
+        let mut rights: [[Option<Square>; 2]; 2] = [[None; 2]; 2];
+        for (rook, color) in castling {
+            let king = if color.is_white() { white_king } else { black_king };
+            if rook.rank() != Rank::FIRST.relative_to(color) || king.rank() != rook.rank() {
+                return Err(DecodeError::Castling(rook));
+            }
+            // the king side right first, as castling_rooks has them
+            let right = &mut rights[color.index()][usize::from(rook.file() < king.file())];
+            if right.is_some() {
+                return Err(DecodeError::Castling(rook));
+            }
+            *right = Some(rook);
+        }
+
+        let flags = bytes[24];
+        if flags & !(BLACK_TO_MOVE | CHESS960) != 0 {
+            return Err(DecodeError::Flags(flags));
+        }
+        let side_to_move = if flags & BLACK_TO_MOVE == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+
+        // the square a pawn skipped over, so the sixth rank of the side that can take it
+        let enpassant_square = match bytes[25] {
+            NO_ENPASSANT => None,
+            index if index < 64 => {
+                let square = Square::from_index(index as usize);
+                if square.rank().relative_to(side_to_move) != Rank::SIXTH {
+                    return Err(DecodeError::EnPassant(index));
+                }
+                Some(square)
+            }
+            index => return Err(DecodeError::EnPassant(index)),
+        };
+
+        let [[white_ks, white_qs], [black_ks, black_qs]] = rights;
+        let mut board = Board {
+            board: squares,
+            side_to_move,
+            white_ks,
+            white_qs,
+            black_ks,
+            black_qs,
+            chess960: flags & CHESS960 != 0,
+            enpassant_square,
+            halfmoves: u16::from_le_bytes([bytes[26], bytes[27]]),
+            fullmoves: u16::from_le_bytes([bytes[28], bytes[29]]),
+            king_squares: [white_king, black_king],
+            precomputed_move_data: compute_distances(),
+            hash: 0,
+        };
+        board.hash = generate_hash(&board);
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeError, ENCODED_SIZE};
+    use crate::game::board::{Board, STARTPOS};
+    use crate::game::movegen::generate_legal_moves;
+
+    fn assert_round_trip(board: &mut Board, depth: u8) {
+        let bytes = board.encode().unwrap();
+        let decoded = Board::decode(&bytes).unwrap();
+        assert_eq!(decoded.shredder_fen(), board.shredder_fen());
+        assert_eq!(decoded.chess960, board.chess960);
+        assert_eq!(decoded.encode(), Some(bytes));
+
+        if depth > 0 {
+            for m in generate_legal_moves(board) {
+                let undo = board.push(&m);
+                assert_round_trip(board, depth - 1);
+                undo(board);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for fen in [
+            STARTPOS,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "1r2k1r1/8/8/8/8/8/8/RR2K3 w Bk - 99 300",
+        ] {
+            assert_round_trip(&mut Board::new(fen), 2);
+        }
+
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        let mut board = Board::new(fen);
+        board.chess960 = true;
+        assert_round_trip(&mut board, 2);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut bytes = Board::new(STARTPOS).encode().unwrap();
+        assert_eq!(bytes.len(), ENCODED_SIZE);
+
+        // the a8 rook's nibble is empty
+        bytes[8] &= 0xf0;
+        assert_eq!(Board::decode(&bytes).err(), Some(DecodeError::Piece(0)));
+
+        let mut bytes = Board::new("4k3/8/8/8/8/8/8/4K3 w - - 0 1").encode().unwrap();
+        bytes[8] = 0x16;
+        assert_eq!(Board::decode(&bytes).err(), Some(DecodeError::Kings));
+
+        let mut bytes = Board::new(STARTPOS).encode().unwrap();
+        // e4, where no pawn can be taken en passant
+        bytes[25] = 36;
+        assert_eq!(Board::decode(&bytes).err(), Some(DecodeError::EnPassant(36)));
+        bytes[25] = 64;
+        bytes[24] = 4;
+        assert_eq!(Board::decode(&bytes).err(), Some(DecodeError::Flags(4)));
+    }
+}
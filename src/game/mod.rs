@@ -1,3 +1,10 @@
+//! The rules of the game: the board, move generation and perft.
+
 pub mod board;
+pub mod color;
+pub mod encoding;
 pub mod movegen;
+pub mod movelist;
 pub mod perft;
+pub mod render;
+pub mod square;
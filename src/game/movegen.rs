@@ -1,6 +1,12 @@
-use crate::game::board::{parse_square, SQUARES};
-
-use super::board::{self, Board, Move, Piece, PieceTypes};
+//! Pseudo-legal and legal move generation and attack detection.
+
+use super::board::{
+    self, castling_destinations, Board, Move, Piece, PieceTypes, CAPTURE, DOUBLE_PUSH, EP_CAPTURE,
+    KING_CASTLE, KNIGHT_PROMOTION, KNIGHT_PROMOTION_CAPTURE, QUEEN_CASTLE, QUEEN_PROMOTION, QUIET,
+};
+use super::color::Color;
+use super::movelist::MoveList;
+use super::square::{Rank, Square};
 use std::cmp;
 
 const NORTH: u8 = 0b00000001;
@@ -14,8 +20,57 @@ const SE: u8 = 0b01000000;
 const SW: u8 = 0b10000000;
 
 const HORIZONTAL: u8 = 0b00001111;
-const VERTICAL: u8 = 0b11110000;
 
+// (file offset, rank offset)
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+// (direction flag, index step) for every ray a sliding piece can move along
+const RAYS: [(u8, i8); 8] = [
+    (NORTH, -8),
+    (SOUTH, 8),
+    (EAST, 1),
+    (WEST, -1),
+    (NE, -7),
+    (NW, -9),
+    (SE, 9),
+    (SW, 7),
+];
+
+impl MoveData {
+    fn distance(&self, direction: u8) -> u8 {
+        match direction {
+            NORTH => self.n,
+            SOUTH => self.s,
+            EAST => self.e,
+            WEST => self.w,
+            NE => self.ne,
+            NW => self.nw,
+            SE => self.se,
+            _ => self.sw,
+        }
+    }
+}
+
+/// how many squares there are from a square to the edge of the board in every direction
 #[derive(Clone, Copy, Debug)]
 pub struct MoveData {
     n: u8,
@@ -29,7 +84,7 @@ pub struct MoveData {
     sw: u8,
 }
 
-pub fn compute_distances() -> [MoveData; 64] {
+pub(crate) fn compute_distances() -> [MoveData; 64] {
     let mut distances = [MoveData {
         n: 0,
         e: 0,
@@ -41,1097 +96,634 @@ pub fn compute_distances() -> [MoveData; 64] {
         sw: 0,
     }; 64];
 
-    let mut i: u8 = 0;
-    for _square in distances {
-        let rank = i / 8;
-        let file = i % 8;
-
-        distances[i as usize].n = rank;
-        distances[i as usize].s = 7 - rank;
-        distances[i as usize].w = file;
-        distances[i as usize].e = 7 - file;
+    for (i, distance) in distances.iter_mut().enumerate() {
+        let rank = i as u8 / 8;
+        let file = i as u8 % 8;
 
-        distances[i as usize].ne = cmp::min(distances[i as usize].n, distances[i as usize].e);
-        distances[i as usize].nw = cmp::min(distances[i as usize].n, distances[i as usize].w);
-        distances[i as usize].se = cmp::min(distances[i as usize].s, distances[i as usize].e);
-        distances[i as usize].sw = cmp::min(distances[i as usize].s, distances[i as usize].w);
+        distance.n = rank;
+        distance.s = 7 - rank;
+        distance.w = file;
+        distance.e = 7 - file;
 
-        i += 1;
+        distance.ne = cmp::min(distance.n, distance.e);
+        distance.nw = cmp::min(distance.n, distance.w);
+        distance.se = cmp::min(distance.s, distance.e);
+        distance.sw = cmp::min(distance.s, distance.w);
     }
 
     distances
 }
 
-pub fn generate_pseudolegal_moves(board: &Board) -> Vec<Move> {
-    let mut moves: Vec<Move> = Vec::new();
-    let mut curr_square: u8 = 0;
-    for piece in board.board {
-        match piece.piece_type {
-            board::PieceTypes::Empty => {}
-            board::PieceTypes::Pawn => {
-                pawn_moves(&mut moves, board, &piece, curr_square);
-                pawn_captures(&mut moves, board, &piece, curr_square);
-            }
-            board::PieceTypes::Bishop => {
-                bishop_moves(&mut moves, board, &piece, curr_square);
-            }
-            board::PieceTypes::Knight => {
-                knight_moves(&mut moves, board, &piece, curr_square);
-            }
-            board::PieceTypes::Rook => {
-                rook_moves(&mut moves, board, &piece, curr_square);
-            }
-            board::PieceTypes::Queen => {
-                queen_moves(&mut moves, board, &piece, curr_square);
-            }
-            board::PieceTypes::King => {
-                king_moves(&mut moves, board, &piece, curr_square);
-            }
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stage {
+    // captures, en passant and queen promotions
+    Captures,
+    // everything else, including castling and underpromotions
+    Quiets,
+    // non-king moves that capture or block the single checking piece
+    Evasions,
+}
+
+fn generate(board: &Board, moves: &mut MoveList, stage: Stage, targets: u64) {
+    for curr_square in Square::all() {
+        let piece = board.board[curr_square.index()];
+        if piece.piece_type != PieceTypes::Empty && piece.color == board.side_to_move {
+            generate_square(board, moves, stage, targets, &piece, curr_square);
         }
-        curr_square += 1;
     }
-    moves
 }
 
-pub fn generate_legal_moves(board: &mut Board) -> Vec<Move> {
-    let pseudo_moves = generate_pseudolegal_moves(board);
-    let mut legal_moves: Vec<Move> = Vec::new();
-    for m in pseudo_moves {
-        // println!("{}", m.uci());
-        let undo = board.push(&m);
-
-        if board.wtomove {
-            board.wtomove = !board.wtomove;
-            if !is_check(board, board.bkingpos, false) {
-                legal_moves.push(m);
+fn generate_square(
+    board: &Board,
+    moves: &mut MoveList,
+    stage: Stage,
+    targets: u64,
+    piece: &Piece,
+    curr_square: Square,
+) {
+    match piece.piece_type {
+        board::PieceTypes::Empty => {}
+        board::PieceTypes::Pawn => {
+            pawn_moves(moves, board, piece, curr_square, stage, targets);
+            pawn_captures(moves, board, piece, curr_square, stage, targets);
+        }
+        board::PieceTypes::Bishop => {
+            bishop_moves(moves, board, piece, curr_square, targets);
+        }
+        board::PieceTypes::Knight => {
+            knight_moves(moves, board, piece, curr_square, targets);
+        }
+        board::PieceTypes::Rook => {
+            rook_moves(moves, board, piece, curr_square, targets);
+        }
+        board::PieceTypes::Queen => {
+            queen_moves(moves, board, piece, curr_square, targets);
+        }
+        board::PieceTypes::King => {
+            if stage != Stage::Evasions {
+                king_moves(moves, board, piece, curr_square, targets);
             }
-            board.wtomove = !board.wtomove;
-        } else {
-            board.wtomove = !board.wtomove;
-            if !is_check(board, board.wkingpos, true) {
-                legal_moves.push(m);
+            if stage == Stage::Quiets {
+                castling_moves(moves, board, piece, curr_square);
             }
-            board.wtomove = !board.wtomove;
         }
-
-        board.wtomove = !board.wtomove;
-        undo(board);
     }
+}
 
-    legal_moves
+/// pseudo-legal moves of the piece on curr_square, if it belongs to the side to move
+pub fn generate_piece_moves(board: &Board, curr_square: Square, moves: &mut MoveList) {
+    let piece = board.board[curr_square.index()];
+    if piece.piece_type == PieceTypes::Empty || piece.color != board.side_to_move {
+        return;
+    }
+    let enemies = occupancy(board, !board.side_to_move);
+    generate_square(board, moves, Stage::Captures, enemies, &piece, curr_square);
+    generate_square(board, moves, Stage::Quiets, empty_squares(board), &piece, curr_square);
 }
 
-pub fn generate_legal_captures(board: &mut Board) -> Vec<Move> {
-    let pseudo_moves = generate_pseudolegal_moves(board);
-    let mut legal_moves: Vec<Move> = Vec::new();
-
-    for m in pseudo_moves {
-        // println!("{}", m.uci());
-        let move_to = board.board[m.to as usize].piece_type;
-        let undo = board.push(&m);
-        if move_to != PieceTypes::Empty {
-            if board.wtomove {
-                board.wtomove = !board.wtomove;
-                if !is_check(board, board.bkingpos, false) {
-                    legal_moves.push(m);
-                }
-                board.wtomove = !board.wtomove;
-            } else {
-                board.wtomove = !board.wtomove;
-                if !is_check(board, board.wkingpos, true) {
-                    legal_moves.push(m);
-                }
-                board.wtomove = !board.wtomove;
-            }
+/// a bitboard of the squares `color` has a piece on
+pub fn occupancy(board: &Board, color: Color) -> u64 {
+    let mut occupied = 0u64;
+    for (square, piece) in board.board.iter().enumerate() {
+        if piece.piece_type != PieceTypes::Empty && piece.color == color {
+            occupied |= 1 << square;
         }
-
-        board.wtomove = !board.wtomove;
-        undo(board);
     }
+    occupied
+}
 
-    legal_moves
+fn empty_squares(board: &Board) -> u64 {
+    !(occupancy(board, Color::White) | occupancy(board, Color::Black))
 }
 
-fn pawn_moves(moves: &mut Vec<Move>, board: &Board, piece: &Piece, curr_square: u8) {
-    if piece.white
-        && board.wtomove
-        && board.board[curr_square as usize - 8].piece_type == PieceTypes::Empty
-    {
-        if curr_square / 8 == 6 {
-            if board.board[curr_square as usize - 16].piece_type == PieceTypes::Empty {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 16,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 8,
-                promotion: PieceTypes::Empty,
-            });
-        } else if curr_square / 8 == 1 {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 8,
-                promotion: PieceTypes::Knight,
-            });
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 8,
-                promotion: PieceTypes::Bishop,
-            });
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 8,
-                promotion: PieceTypes::Rook,
-            });
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 8,
-                promotion: PieceTypes::Queen,
-            });
-        } else {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 8,
-                promotion: PieceTypes::Empty,
-            });
-        }
-    } else if !piece.white
-        && !board.wtomove
-        && board.board[curr_square as usize + 8].piece_type == PieceTypes::Empty
-    {
-        if curr_square / 8 == 1 {
-            if board.board[curr_square as usize + 16].piece_type == PieceTypes::Empty {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 16,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 8,
-                promotion: PieceTypes::Empty,
-            });
-        } else if curr_square / 8 == 6 {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 8,
-                promotion: PieceTypes::Knight,
-            });
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 8,
-                promotion: PieceTypes::Bishop,
-            });
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 8,
-                promotion: PieceTypes::Rook,
-            });
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 8,
-                promotion: PieceTypes::Queen,
-            });
-        } else {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 8,
-                promotion: PieceTypes::Empty,
-            });
-        }
+/// pseudo-legal captures, en passant captures and promotions to a queen
+pub fn generate_captures(board: &Board, moves: &mut MoveList) {
+    let enemies = occupancy(board, !board.side_to_move);
+    generate(board, moves, Stage::Captures, enemies);
+}
+
+/// pseudo-legal moves that are not generated by generate_captures
+pub fn generate_quiets(board: &Board, moves: &mut MoveList) {
+    generate(board, moves, Stage::Quiets, empty_squares(board));
+}
+
+/// pseudo-legal moves for a side in check: king moves, and captures or blocks of a single checker
+pub fn generate_evasions(board: &Board, moves: &mut MoveList) {
+    let color = board.side_to_move;
+    let king_square = board.king_square(color);
+    let king = board.board[king_square.index()];
+
+    king_moves(moves, board, &king, king_square, !occupancy(board, color));
+
+    let checkers = attackers(board, king_square, color);
+    if checkers.count_ones() != 1 {
+        return;
     }
+    let checker = Square::from_index(checkers.trailing_zeros() as usize);
+    generate(
+        board,
+        moves,
+        Stage::Evasions,
+        checkers | between(king_square, checker),
+    );
 }
 
-fn pawn_captures(moves: &mut Vec<Move>, board: &Board, piece: &Piece, curr_square: u8) {
-    if piece.white && board.wtomove {
-        if curr_square % 8 == 0
-            && ((board.board[curr_square as usize - 7].white == false
-                && board.board[curr_square as usize - 7].piece_type != PieceTypes::Empty)
-                || curr_square - 7 == board.enpassant_square)
-        {
-            if curr_square / 8 == 1 {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 7,
-                    promotion: PieceTypes::Knight,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 7,
-                    promotion: PieceTypes::Bishop,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 7,
-                    promotion: PieceTypes::Rook,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 7,
-                    promotion: PieceTypes::Queen,
-                });
-            } else {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 7,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-            return;
-        }
-        if curr_square % 8 == 7
-            && ((board.board[curr_square as usize - 9].white == false
-                && board.board[curr_square as usize - 9].piece_type != PieceTypes::Empty)
-                || curr_square - 9 == board.enpassant_square)
-        {
-            if curr_square / 8 == 1 {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 9,
-                    promotion: PieceTypes::Knight,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 9,
-                    promotion: PieceTypes::Bishop,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 9,
-                    promotion: PieceTypes::Rook,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 9,
-                    promotion: PieceTypes::Queen,
-                });
-            } else {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 9,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-            return;
-        }
-        if curr_square % 8 != 0 {
-            if board.board[curr_square as usize - 9].white == false
-                && board.board[curr_square as usize - 9].piece_type != PieceTypes::Empty
-            {
-                if curr_square / 8 == 1 {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 9,
-                        promotion: PieceTypes::Knight,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 9,
-                        promotion: PieceTypes::Bishop,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 9,
-                        promotion: PieceTypes::Rook,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 9,
-                        promotion: PieceTypes::Queen,
-                    });
-                } else {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 9,
-                        promotion: PieceTypes::Empty,
-                    });
-                }
-            }
-        }
-        if curr_square % 8 != 7 {
-            if board.board[curr_square as usize - 7].white == false
-                && board.board[curr_square as usize - 7].piece_type != PieceTypes::Empty
-            {
-                if curr_square / 8 == 1 {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 7,
-                        promotion: PieceTypes::Knight,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 7,
-                        promotion: PieceTypes::Bishop,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 7,
-                        promotion: PieceTypes::Rook,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 7,
-                        promotion: PieceTypes::Queen,
-                    });
-                } else {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - 7,
-                        promotion: PieceTypes::Empty,
-                    });
-                }
-            }
-        }
-        if curr_square % 8 != 7 {
-            if curr_square - 7 == board.enpassant_square {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 7,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-        }
-        if curr_square % 8 != 0 {
-            if curr_square - 9 == board.enpassant_square {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 9,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-        }
-    } else if !piece.white && !board.wtomove {
-        if curr_square % 8 == 0
-            && ((board.board[curr_square as usize + 9].white
-                && board.board[curr_square as usize + 9].piece_type != PieceTypes::Empty)
-                || curr_square + 9 == board.enpassant_square)
-        {
-            if curr_square / 8 == 6 {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 9,
-                    promotion: PieceTypes::Knight,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 9,
-                    promotion: PieceTypes::Bishop,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 9,
-                    promotion: PieceTypes::Rook,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 9,
-                    promotion: PieceTypes::Queen,
-                });
-            } else {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 9,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-            return;
-        }
-        if curr_square % 8 == 7
-            && ((board.board[curr_square as usize + 7].white
-                && board.board[curr_square as usize + 7].piece_type != PieceTypes::Empty)
-                || curr_square + 7 == board.enpassant_square)
-        {
-            if curr_square / 8 == 6 {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 7,
-                    promotion: PieceTypes::Knight,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 7,
-                    promotion: PieceTypes::Bishop,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 7,
-                    promotion: PieceTypes::Rook,
-                });
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 7,
-                    promotion: PieceTypes::Queen,
-                });
-            } else {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 7,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-            return;
-        }
-        if curr_square % 8 != 7 {
-            if board.board[curr_square as usize + 9].white
-                && board.board[curr_square as usize + 9].piece_type != PieceTypes::Empty
-            {
-                if curr_square / 8 == 6 {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 9,
-                        promotion: PieceTypes::Knight,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 9,
-                        promotion: PieceTypes::Bishop,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 9,
-                        promotion: PieceTypes::Rook,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 9,
-                        promotion: PieceTypes::Queen,
-                    });
-                } else {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 9,
-                        promotion: PieceTypes::Empty,
-                    });
-                }
-            }
-        }
-        if curr_square % 8 != 0 {
-            if board.board[curr_square as usize + 7].white
-                && board.board[curr_square as usize + 7].piece_type != PieceTypes::Empty
-            {
-                if curr_square / 8 == 6 {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 7,
-                        promotion: PieceTypes::Knight,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 7,
-                        promotion: PieceTypes::Bishop,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 7,
-                        promotion: PieceTypes::Rook,
-                    });
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 7,
-                        promotion: PieceTypes::Queen,
-                    });
-                } else {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + 7,
-                        promotion: PieceTypes::Empty,
-                    });
-                }
-            }
-        }
-        if curr_square % 8 != 0 {
-            if curr_square + 7 == board.enpassant_square {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 7,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-        }
-        if curr_square % 8 != 7 {
-            if curr_square + 9 == board.enpassant_square {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 9,
-                    promotion: PieceTypes::Empty,
-                });
-            }
+/// pseudo-legal quiet moves that give check, directly or by uncovering a slider
+pub fn generate_quiet_checks(board: &Board, moves: &mut MoveList) {
+    let mut quiets = MoveList::new();
+    generate_quiets(board, &mut quiets);
+    for m in quiets {
+        if board.gives_check(&m) {
+            moves.push(m);
         }
     }
 }
 
-fn knight_moves(moves: &mut Vec<Move>, board: &Board, piece: &Piece, curr_square: u8) {
-    let rank = curr_square / 8;
-    let file = curr_square % 8;
-    if rank > 1 {
-        if file > 0
-            && (board.board[curr_square as usize - 17].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize - 17].white != board.wtomove)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 17,
-                promotion: PieceTypes::Empty,
-            });
-        }
-        if file < 7
-            && (board.board[curr_square as usize - 15].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize - 15].white != board.wtomove)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 15,
-                promotion: PieceTypes::Empty,
-            });
-        }
+/// every move the side to move could make if its king could be left in check
+pub fn generate_pseudolegal_moves(board: &Board) -> MoveList {
+    let mut moves = MoveList::new();
+    generate_captures(board, &mut moves);
+    generate_quiets(board, &mut moves);
+    moves
+}
+
+/// all legal moves of the side to move
+pub fn generate_legal_moves(board: &mut Board) -> MoveList {
+    let mut moves = MoveList::new();
+    if in_check(board) {
+        generate_evasions(board, &mut moves);
+    } else {
+        generate_captures(board, &mut moves);
+        generate_quiets(board, &mut moves);
     }
+    moves.retain(|m| leaves_king_safe(board, m));
+    moves
+}
 
-    if rank < 6 {
-        if file > 0
-            && (board.board[curr_square as usize + 15].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize + 15].white != board.wtomove)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 15,
-                promotion: PieceTypes::Empty,
-            });
-        }
-        if file < 7
-            && (board.board[curr_square as usize + 17].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize + 17].white != board.wtomove)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 17,
-                promotion: PieceTypes::Empty,
-            });
-        }
+/// the legal moves generate_captures would return
+pub fn generate_legal_captures(board: &mut Board) -> MoveList {
+    let mut moves = MoveList::new();
+    generate_captures(board, &mut moves);
+    moves.retain(|m| leaves_king_safe(board, m));
+    moves
+}
+
+/// whether the side to move is in check
+pub fn in_check(board: &Board) -> bool {
+    let color = board.side_to_move;
+    is_check(board, board.king_square(color), color)
+}
+
+fn leaves_king_safe(board: &mut Board, m: &Move) -> bool {
+    let color = board.side_to_move;
+    let undo = board.push(m);
+    let safe = !is_check(board, board.king_square(color), color);
+    undo(board);
+    safe
+}
+
+// squares strictly between two squares on the same rank, file or diagonal
+fn between(a: Square, b: Square) -> u64 {
+    let rank_diff = b.rank().index() as i8 - a.rank().index() as i8;
+    let file_diff = b.file().index() as i8 - a.file().index() as i8;
+    if rank_diff != 0 && file_diff != 0 && rank_diff.abs() != file_diff.abs() {
+        return 0;
     }
 
-    if file > 1 {
-        if rank > 0
-            && (board.board[curr_square as usize - 10].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize - 10].white != board.wtomove)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 10,
-                promotion: PieceTypes::Empty,
-            });
-        }
-        if rank < 7
-            && (board.board[curr_square as usize + 6].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize + 6].white != board.wtomove)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 6,
-                promotion: PieceTypes::Empty,
-            });
-        }
+    let mut squares = 0u64;
+    let mut square = a.offset(file_diff.signum(), rank_diff.signum());
+    while let Some(curr) = square.filter(|&curr| curr != b) {
+        squares |= curr.bit();
+        square = curr.offset(file_diff.signum(), rank_diff.signum());
     }
+    squares
+}
 
-    if file < 6 {
-        if rank > 0
-            && (board.board[curr_square as usize - 6].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize - 6].white != board.wtomove)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 6,
-                promotion: PieceTypes::Empty,
-            });
-        }
-        if rank < 7
-            && (board.board[curr_square as usize + 10].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize + 10].white != board.wtomove)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 10,
-                promotion: PieceTypes::Empty,
-            });
-        }
+fn add_promotions(moves: &mut MoveList, from: Square, to: Square, capture: bool) {
+    let first = if capture {
+        KNIGHT_PROMOTION_CAPTURE
+    } else {
+        KNIGHT_PROMOTION
+    };
+    for flags in first..first + 4 {
+        moves.push(Move::new(from, to, flags));
     }
 }
 
-fn sliding_piece_moves(
-    moves: &mut Vec<Move>,
+// adds a move to `to` if it is a target and not occupied by one of the mover's own pieces
+fn add_step(
+    moves: &mut MoveList,
     board: &Board,
     piece: &Piece,
-    curr_square: u8,
-    direction: u8,
+    from: Square,
+    to: Square,
+    targets: u64,
 ) {
-    if piece.white == board.wtomove {
-        if direction & NORTH == NORTH {
-            for i in 1..board.precomputed_move_data[curr_square as usize].n as usize + 1 {
-                if board.board[curr_square as usize - i * 8].white != board.wtomove
-                    && board.board[curr_square as usize - i * 8].piece_type != PieceTypes::Empty
-                {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - i as u8 * 8,
-                        promotion: PieceTypes::Empty,
-                    });
-                    break;
-                }
-
-                if board.board[curr_square as usize - i * 8].white == board.wtomove
-                    && board.board[curr_square as usize - i * 8].piece_type != PieceTypes::Empty
-                {
-                    break;
-                }
-
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - i as u8 * 8,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-        }
+    if targets & to.bit() == 0 {
+        return;
+    }
+    let target = board.board[to.index()];
+    if target.piece_type == PieceTypes::Empty {
+        moves.push(Move::new(from, to, QUIET));
+    } else if target.color != piece.color {
+        moves.push(Move::new(from, to, CAPTURE));
+    }
+}
 
-        if direction & SOUTH == SOUTH {
-            for i in 1..board.precomputed_move_data[curr_square as usize].s as usize + 1 {
-                if board.board[curr_square as usize + i * 8].white != board.wtomove
-                    && board.board[curr_square as usize + i * 8].piece_type != PieceTypes::Empty
-                {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + i as u8 * 8,
-                        promotion: PieceTypes::Empty,
-                    });
-                    break;
-                }
+fn pawn_moves(
+    moves: &mut MoveList,
+    board: &Board,
+    piece: &Piece,
+    curr_square: Square,
+    stage: Stage,
+    targets: u64,
+) {
+    let Some(to) = curr_square.forward(piece.color) else {
+        return;
+    };
+    if board.board[to.index()].piece_type != PieceTypes::Empty {
+        return;
+    }
 
-                if board.board[curr_square as usize + i * 8].white == board.wtomove
-                    && board.board[curr_square as usize + i * 8].piece_type != PieceTypes::Empty
-                {
-                    break;
+    let relative_rank = curr_square.rank().relative_to(piece.color);
+    if relative_rank == Rank::SEVENTH {
+        match stage {
+            Stage::Captures => moves.push(Move::new(curr_square, to, QUEEN_PROMOTION)),
+            Stage::Quiets => {
+                for flags in KNIGHT_PROMOTION..QUEEN_PROMOTION {
+                    moves.push(Move::new(curr_square, to, flags));
                 }
-
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + i as u8 * 8,
-                    promotion: PieceTypes::Empty,
-                });
             }
-        }
-
-        if direction & EAST == EAST {
-            for i in 1..board.precomputed_move_data[curr_square as usize].e as usize + 1 {
-                if board.board[curr_square as usize + i].white != board.wtomove
-                    && board.board[curr_square as usize + i].piece_type != PieceTypes::Empty
-                {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + i as u8,
-                        promotion: PieceTypes::Empty,
-                    });
-                    break;
-                }
-
-                if board.board[curr_square as usize + i].white == board.wtomove
-                    && board.board[curr_square as usize + i].piece_type != PieceTypes::Empty
-                {
-                    break;
+            Stage::Evasions => {
+                if targets & to.bit() != 0 {
+                    add_promotions(moves, curr_square, to, false);
                 }
-
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + i as u8,
-                    promotion: PieceTypes::Empty,
-                });
             }
         }
+        return;
+    }
 
-        if direction & WEST == WEST {
-            for i in 1..board.precomputed_move_data[curr_square as usize].w as usize + 1 {
-                if board.board[curr_square as usize - i].white != board.wtomove
-                    && board.board[curr_square as usize - i].piece_type != PieceTypes::Empty
-                {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - i as u8,
-                        promotion: PieceTypes::Empty,
-                    });
-                    break;
-                }
+    if stage == Stage::Captures {
+        return;
+    }
 
-                if board.board[curr_square as usize - i].white == board.wtomove
-                    && board.board[curr_square as usize - i].piece_type != PieceTypes::Empty
-                {
-                    break;
-                }
+    if targets & to.bit() != 0 {
+        moves.push(Move::new(curr_square, to, QUIET));
+    }
 
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - i as u8,
-                    promotion: PieceTypes::Empty,
-                });
+    if relative_rank == Rank::SECOND {
+        if let Some(double_to) = to.forward(piece.color) {
+            if board.board[double_to.index()].piece_type == PieceTypes::Empty
+                && targets & double_to.bit() != 0
+            {
+                moves.push(Move::new(curr_square, double_to, DOUBLE_PUSH));
             }
         }
+    }
+}
 
-        if direction & NE == NE {
-            for i in 1..board.precomputed_move_data[curr_square as usize].ne as usize + 1 {
-                if board.board[curr_square as usize - i * 7].white != board.wtomove
-                    && board.board[curr_square as usize - i * 7].piece_type != PieceTypes::Empty
-                {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - i as u8 * 7,
-                        promotion: PieceTypes::Empty,
-                    });
-                    break;
-                }
-
-                if board.board[curr_square as usize - i * 7].white == board.wtomove
-                    && board.board[curr_square as usize - i * 7].piece_type != PieceTypes::Empty
-                {
-                    break;
-                }
-
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - i as u8 * 7,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-        }
+fn pawn_captures(
+    moves: &mut MoveList,
+    board: &Board,
+    piece: &Piece,
+    curr_square: Square,
+    stage: Stage,
+    targets: u64,
+) {
+    if stage == Stage::Quiets {
+        return;
+    }
 
-        if direction & NW == NW {
-            for i in 1..board.precomputed_move_data[curr_square as usize].nw as usize + 1 {
-                if board.board[curr_square as usize - i * 9].white != board.wtomove
-                    && board.board[curr_square as usize - i * 9].piece_type != PieceTypes::Empty
-                {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square - i as u8 * 9,
-                        promotion: PieceTypes::Empty,
-                    });
-                    break;
-                }
+    let promotes = curr_square.rank().relative_to(piece.color) == Rank::SEVENTH;
+    let Some(ahead) = curr_square.forward(piece.color) else {
+        return;
+    };
 
-                if board.board[curr_square as usize - i * 9].white == board.wtomove
-                    && board.board[curr_square as usize - i * 9].piece_type != PieceTypes::Empty
-                {
-                    break;
+    for file_offset in [-1, 1] {
+        if let Some(to) = ahead.offset(file_offset, 0) {
+            let target = board.board[to.index()];
+            if target.piece_type != PieceTypes::Empty && target.color != piece.color {
+                if targets & to.bit() == 0 {
+                    continue;
                 }
-
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - i as u8 * 9,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-        }
-
-        if direction & SE == SE {
-            for i in 1..board.precomputed_move_data[curr_square as usize].se as usize + 1 {
-                if board.board[curr_square as usize + i * 9].white != board.wtomove
-                    && board.board[curr_square as usize + i * 9].piece_type != PieceTypes::Empty
-                {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + i as u8 * 9,
-                        promotion: PieceTypes::Empty,
-                    });
-                    break;
+                if promotes {
+                    add_promotions(moves, curr_square, to, true);
+                } else {
+                    moves.push(Move::new(curr_square, to, CAPTURE));
                 }
-
-                if board.board[curr_square as usize + i * 9].white == board.wtomove
-                    && board.board[curr_square as usize + i * 9].piece_type != PieceTypes::Empty
-                {
-                    break;
+            } else if Some(to) == board.enpassant_square {
+                // the captured pawn sits beside the capturing one, it may be the checker
+                let captured = to.backward(piece.color).unwrap();
+                if targets & (to.bit() | captured.bit()) != 0 {
+                    moves.push(Move::new(curr_square, to, EP_CAPTURE));
                 }
-
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + i as u8 * 9,
-                    promotion: PieceTypes::Empty,
-                });
             }
         }
+    }
+}
 
-        if direction & SW == SW {
-            for i in 1..board.precomputed_move_data[curr_square as usize].sw as usize + 1 {
-                if board.board[curr_square as usize + i * 7].white != board.wtomove
-                    && board.board[curr_square as usize + i * 7].piece_type != PieceTypes::Empty
-                {
-                    moves.push(Move {
-                        from: curr_square,
-                        to: curr_square + i as u8 * 7,
-                        promotion: PieceTypes::Empty,
-                    });
-                    break;
-                }
+fn knight_moves(
+    moves: &mut MoveList,
+    board: &Board,
+    piece: &Piece,
+    curr_square: Square,
+    targets: u64,
+) {
+    for (file_offset, rank_offset) in KNIGHT_OFFSETS {
+        if let Some(to) = curr_square.offset(file_offset, rank_offset) {
+            add_step(moves, board, piece, curr_square, to, targets);
+        }
+    }
+}
 
-                if board.board[curr_square as usize + i * 7].white == board.wtomove
-                    && board.board[curr_square as usize + i * 7].piece_type != PieceTypes::Empty
-                {
-                    break;
+fn sliding_piece_moves(
+    moves: &mut MoveList,
+    board: &Board,
+    piece: &Piece,
+    curr_square: Square,
+    direction: u8,
+    targets: u64,
+) {
+    for (ray, step) in RAYS {
+        if direction & ray != ray {
+            continue;
+        }
+
+        let distance = board.precomputed_move_data[curr_square.index()].distance(ray);
+        let mut index = curr_square.index() as i8;
+        for _ in 0..distance {
+            index += step;
+            let to = Square::from_index(index as usize);
+            let target = board.board[to.index()];
+            if target.piece_type == PieceTypes::Empty {
+                if targets & to.bit() != 0 {
+                    moves.push(Move::new(curr_square, to, QUIET));
                 }
-
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + i as u8 * 7,
-                    promotion: PieceTypes::Empty,
-                });
+                continue;
             }
+            if target.color != piece.color && targets & to.bit() != 0 {
+                moves.push(Move::new(curr_square, to, CAPTURE));
+            }
+            break;
         }
     }
 }
 
-fn bishop_moves(moves: &mut Vec<Move>, board: &Board, piece: &Piece, curr_square: u8) {
-    sliding_piece_moves(moves, board, piece, curr_square, 0b11110000);
+fn bishop_moves(
+    moves: &mut MoveList,
+    board: &Board,
+    piece: &Piece,
+    curr_square: Square,
+    targets: u64,
+) {
+    sliding_piece_moves(moves, board, piece, curr_square, 0b11110000, targets);
 }
 
-fn rook_moves(moves: &mut Vec<Move>, board: &Board, piece: &Piece, curr_square: u8) {
-    sliding_piece_moves(moves, board, piece, curr_square, 0b00001111);
+fn rook_moves(moves: &mut MoveList, board: &Board, piece: &Piece, curr_square: Square, targets: u64) {
+    sliding_piece_moves(moves, board, piece, curr_square, 0b00001111, targets);
 }
 
-fn queen_moves(moves: &mut Vec<Move>, board: &Board, piece: &Piece, curr_square: u8) {
-    sliding_piece_moves(moves, board, piece, curr_square, 0b11111111);
+fn queen_moves(
+    moves: &mut MoveList,
+    board: &Board,
+    piece: &Piece,
+    curr_square: Square,
+    targets: u64,
+) {
+    sliding_piece_moves(moves, board, piece, curr_square, 0b11111111, targets);
 }
 
-fn king_moves(moves: &mut Vec<Move>, board: &Board, piece: &Piece, curr_square: u8) {
-    let rank = curr_square / 8;
-    let file = curr_square % 8;
-
-    if rank > 0 {
-        if file > 0
-            && (board.board[curr_square as usize - 9].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize - 9].white != piece.white)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 9,
-                promotion: PieceTypes::Empty,
-            });
+fn king_moves(moves: &mut MoveList, board: &Board, piece: &Piece, curr_square: Square, targets: u64) {
+    for (file_offset, rank_offset) in KING_OFFSETS {
+        if let Some(to) = curr_square.offset(file_offset, rank_offset) {
+            add_step(moves, board, piece, curr_square, to, targets);
         }
+    }
+}
+
+fn castling_moves(moves: &mut MoveList, board: &Board, piece: &Piece, curr_square: Square) {
+    let is_empty = |square: Square| board.board[square.index()].piece_type == PieceTypes::Empty;
+    // all four squares are on the back rank, where the index order is the file order
+    let squares = |a: Square, b: Square| (a.index()..=b.index()).map(Square::from_index);
+
+    // the king and rook may start anywhere on the back rank, so everything between
+    // the start and end squares of both has to be empty apart from the two of them,
+    // and the king may not be in check or pass over an attacked square. the square
+    // the king lands on is covered by the legality check after the move
+    for (rook, flags) in board
+        .castling_rooks(piece.color)
+        .into_iter()
+        .zip([KING_CASTLE, QUEEN_CASTLE])
+    {
+        let Some(rook) = rook else { continue };
+        let (king_to, rook_to) = castling_destinations(curr_square, rook);
 
-        if file < 7
-            && (board.board[curr_square as usize - 7].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize - 7].white != piece.white)
-            && piece.white == board.wtomove
+        let lowest = curr_square.min(rook).min(king_to).min(rook_to);
+        let highest = curr_square.max(rook).max(king_to).max(rook_to);
+        if squares(lowest, highest)
+            .any(|square| square != curr_square && square != rook && !is_empty(square))
         {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 7,
-                promotion: PieceTypes::Empty,
-            });
+            continue;
         }
 
-        if (board.board[curr_square as usize - 8].piece_type == PieceTypes::Empty
-            || board.board[curr_square as usize - 8].white != piece.white)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 8,
-                promotion: PieceTypes::Empty,
-            });
+        let (path_start, path_end) = (curr_square.min(king_to), curr_square.max(king_to));
+        if squares(path_start, path_end).any(|square| is_check(board, square, piece.color)) {
+            continue;
         }
+
+        moves.push(Move::new(curr_square, rook, flags));
     }
+}
 
-    if rank < 7 {
-        if file > 0
-            && (board.board[curr_square as usize + 7].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize + 7].white != piece.white)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 7,
-                promotion: PieceTypes::Empty,
-            });
-        }
+/// returns whether curr_square is attacked by the opponent of `color`
+pub fn is_check(board: &Board, curr_square: Square, color: Color) -> bool {
+    attackers(board, curr_square, color) != 0
+}
 
-        if file < 7
-            && (board.board[curr_square as usize + 9].piece_type == PieceTypes::Empty
-                || board.board[curr_square as usize + 9].white != piece.white)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 9,
-                promotion: PieceTypes::Empty,
-            });
-        }
+/// the squares of the pieces of the opponent of `color` attacking curr_square
+pub fn attackers(board: &Board, curr_square: Square, color: Color) -> u64 {
+    square_attackers(&board.board, &board.precomputed_move_data, curr_square, color)
+}
 
-        if (board.board[curr_square as usize + 8].piece_type == PieceTypes::Empty
-            || board.board[curr_square as usize + 8].white != piece.white)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 8,
-                promotion: PieceTypes::Empty,
-            });
-        }
-    }
+/// like attackers, but for any arrangement of pieces, e.g. one after a move that has not been pushed
+pub fn square_attackers(
+    squares: &[Piece; 64],
+    precomputed_move_data: &[MoveData; 64],
+    curr_square: Square,
+    color: Color,
+) -> u64 {
+    let mut attackers = 0u64;
+    let is_enemy = |square: Square, piece_type: PieceTypes| {
+        let piece = squares[square.index()];
+        piece.piece_type == piece_type && piece.color != color
+    };
 
-    if file > 0 {
-        if (board.board[curr_square as usize - 1].piece_type == PieceTypes::Empty
-            || board.board[curr_square as usize - 1].white != piece.white)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square - 1,
-                promotion: PieceTypes::Empty,
-            });
+    // enemy pawns attack curr_square from the squares diagonally in front of it
+    if let Some(ahead) = curr_square.forward(color) {
+        for file_offset in [-1, 1] {
+            if let Some(square) = ahead.offset(file_offset, 0) {
+                if is_enemy(square, PieceTypes::Pawn) {
+                    attackers |= square.bit();
+                }
+            }
         }
     }
 
-    if file < 7 {
-        if (board.board[curr_square as usize + 1].piece_type == PieceTypes::Empty
-            || board.board[curr_square as usize + 1].white != piece.white)
-            && piece.white == board.wtomove
-        {
-            moves.push(Move {
-                from: curr_square,
-                to: curr_square + 1,
-                promotion: PieceTypes::Empty,
-            });
+    for (file_offset, rank_offset) in KNIGHT_OFFSETS {
+        if let Some(square) = curr_square.offset(file_offset, rank_offset) {
+            if is_enemy(square, PieceTypes::Knight) {
+                attackers |= square.bit();
+            }
         }
     }
 
-    if piece.white && board.wtomove {
-        if board.wkingpos == 60 {
-            if board.board[curr_square as usize + 1].piece_type == PieceTypes::Empty
-                && board.board[curr_square as usize + 2].piece_type == PieceTypes::Empty
-                && board.white_ks
-                && !is_check(board, curr_square + 1, true)
-                && !is_check(board, curr_square, true)
-            {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 2,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-            if board.board[curr_square as usize - 1].piece_type == PieceTypes::Empty
-                && board.board[curr_square as usize - 2].piece_type == PieceTypes::Empty
-                && board.board[curr_square as usize - 3].piece_type == PieceTypes::Empty
-                && board.white_qs
-                && !is_check(board, curr_square - 1, true)
-                && !is_check(board, curr_square, true)
-            {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 2,
-                    promotion: PieceTypes::Empty,
-                });
+    for (file_offset, rank_offset) in KING_OFFSETS {
+        if let Some(square) = curr_square.offset(file_offset, rank_offset) {
+            if is_enemy(square, PieceTypes::King) {
+                attackers |= square.bit();
             }
         }
-    } else if !piece.white && !board.wtomove {
-        if board.bkingpos == 4 {
-            if board.board[curr_square as usize + 1].piece_type == PieceTypes::Empty
-                && board.board[curr_square as usize + 2].piece_type == PieceTypes::Empty
-                && board.black_ks
-                && !is_check(board, curr_square + 1, false)
-                && !is_check(board, curr_square, false)
-            {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square + 2,
-                    promotion: PieceTypes::Empty,
-                });
-            }
-            if board.board[curr_square as usize - 1].piece_type == PieceTypes::Empty
-                && board.board[curr_square as usize - 2].piece_type == PieceTypes::Empty
-                && board.board[curr_square as usize - 3].piece_type == PieceTypes::Empty
-                && board.black_qs
-                && !is_check(board, curr_square - 1, false)
-                && !is_check(board, curr_square, false)
+    }
+
+    for (direction, step) in RAYS {
+        let slider = if direction & HORIZONTAL == direction {
+            PieceTypes::Rook
+        } else {
+            PieceTypes::Bishop
+        };
+        let distance = precomputed_move_data[curr_square.index()].distance(direction);
+        let mut index = curr_square.index() as i8;
+        for _ in 0..distance {
+            index += step;
+            let piece = squares[index as usize];
+            if piece.piece_type == PieceTypes::Empty {
+                continue;
+            }
+            if piece.color != color
+                && (piece.piece_type == slider || piece.piece_type == PieceTypes::Queen)
             {
-                moves.push(Move {
-                    from: curr_square,
-                    to: curr_square - 2,
-                    promotion: PieceTypes::Empty,
-                });
+                attackers |= 1 << index;
             }
+            break;
         }
     }
+
+    attackers
 }
 
-pub fn is_check(board: &Board, curr_square: u8, white: bool) -> bool {
-    let piece = Piece {
-        piece_type: PieceTypes::Empty,
-        white,
+#[cfg(test)]
+mod tests {
+    use super::{
+        generate_captures, generate_evasions, generate_legal_moves, generate_quiet_checks,
+        generate_quiets, in_check,
     };
-
-    let mut moves: Vec<Move> = Vec::new();
-    let mut dmoves: Vec<Move> = Vec::new();
-    let mut hmoves: Vec<Move> = Vec::new();
-    let mut pcaptures: Vec<Move> = Vec::new();
-    let mut kmoves: Vec<Move> = Vec::new();
-
-    sliding_piece_moves(&mut dmoves, board, &piece, curr_square, 0b11110000);
-    sliding_piece_moves(&mut hmoves, board, &piece, curr_square, 0b00001111);
-    if white && curr_square / 8 > 0{
-        pawn_captures(&mut pcaptures, board, &piece, curr_square);
-    } else if !white && curr_square / 8 < 7{
-        pawn_captures(&mut pcaptures, board, &piece, curr_square);
+    use crate::game::board::{Board, Move, QUEEN_PROMOTION, STARTPOS};
+    use crate::game::movelist::MoveList;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    const POSITIONS: [(&str, u8); 6] = [
+        (KIWIPETE, 2),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3),
+        ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 2),
+        ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 2),
+        ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 2),
+        ("4k3/8/8/2KPp2r/8/8/8/8 w - e6 0 1", 3),
+    ];
+
+    fn legal(board: &Board, moves: &MoveList) -> Vec<Move> {
+        moves.iter().copied().filter(|m| board.is_legal(m)).collect()
     }
-    knight_moves(&mut moves, board, &piece, curr_square);
-    king_moves(&mut kmoves, board, &piece, curr_square);
 
-    for m in moves {
-        let piece = board.board[m.to as usize];
-        if piece.piece_type == PieceTypes::Knight && piece.white != white {
-            return true;
+    // the capture and quiet stages split the pseudo-legal moves between them without overlap,
+    // and in check the evasions keep every legal move
+    fn assert_stages(board: &mut Board, depth: u8) {
+        let mut captures = MoveList::new();
+        generate_captures(board, &mut captures);
+        let mut quiets = MoveList::new();
+        generate_quiets(board, &mut quiets);
+
+        for m in captures.iter() {
+            assert!(m.is_capture() || m.flags() == QUEEN_PROMOTION, "{}", m.uci());
+            assert!(!quiets.contains(m), "{} {}", board.fen(), m.uci());
+        }
+        for m in quiets.iter() {
+            assert!(!m.is_capture() && m.flags() != QUEEN_PROMOTION, "{}", m.uci());
+        }
+        let mut all: Vec<Move> = captures.iter().chain(quiets.iter()).copied().collect();
+        let len = all.len();
+        all.sort_by_key(|m| m.uci());
+        all.dedup();
+        assert_eq!(all.len(), len, "{}", board.fen());
+
+        let mut pseudo_legal = captures;
+        for m in quiets {
+            pseudo_legal.push(m);
+        }
+        if in_check(board) {
+            let mut evasions = MoveList::new();
+            generate_evasions(board, &mut evasions);
+            let mut from_evasions = legal(board, &evasions);
+            let mut from_stages = legal(board, &pseudo_legal);
+            from_evasions.sort_by_key(|m| m.uci());
+            from_stages.sort_by_key(|m| m.uci());
+            assert_eq!(from_evasions, from_stages, "{}", board.fen());
+        }
+
+        if depth == 0 {
+            return;
         }
-    }
-
-    for m in dmoves {
-        let piece = board.board[m.to as usize];
-        if (piece.piece_type == PieceTypes::Bishop || piece.piece_type == PieceTypes::Queen)
-            && piece.white != white
-        {
-            return true;
+        for m in generate_legal_moves(board) {
+            let undo = board.push(&m);
+            assert_stages(board, depth - 1);
+            undo(board);
         }
     }
 
-    for m in hmoves {
-        let piece = board.board[m.to as usize];
-        if (piece.piece_type == PieceTypes::Rook || piece.piece_type == PieceTypes::Queen)
-            && piece.white != white
-        {
-            return true;
+    #[test]
+    fn stages() {
+        for (fen, depth) in POSITIONS {
+            assert_stages(&mut Board::new(fen), depth);
         }
     }
 
-    for m in pcaptures {
-        let piece = board.board[m.to as usize];
-        if piece.piece_type == PieceTypes::Pawn
-            && piece.white != white
-            && m.to != board.enpassant_square
-        {
-            return true;
+    // gives_check agrees with pushing the move and looking, and the quiet checks are exactly
+    // the quiet moves that check
+    fn assert_checks(board: &mut Board, depth: u8) {
+        let mut quiets = MoveList::new();
+        generate_quiets(board, &mut quiets);
+        let mut checks = MoveList::new();
+        generate_quiet_checks(board, &mut checks);
+        for m in quiets {
+            let undo = board.push(&m);
+            let checking = in_check(board);
+            undo(board);
+            assert_eq!(checks.contains(&m), checking, "{} {}", board.fen(), m.uci());
+        }
+
+        for m in generate_legal_moves(board) {
+            let gives_check = board.gives_check(&m);
+            let undo = board.push(&m);
+            assert_eq!(gives_check, in_check(board), "{} {}", board.fen(), m.uci());
+            if depth > 1 {
+                assert_checks(board, depth - 1);
+            }
+            undo(board);
         }
     }
 
-    for m in kmoves {
-        let piece = board.board[m.to as usize];
-        if piece.piece_type == PieceTypes::King {
-            return true;
+    #[test]
+    fn checks() {
+        for (fen, depth) in [
+            (STARTPOS, 3),
+            (KIWIPETE, 2),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 2),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 2),
+            ("8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 1", 3),
+            ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", 3),
+        ] {
+            assert_checks(&mut Board::new(fen), depth);
         }
     }
-
-    return false;
 }
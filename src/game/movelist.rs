@@ -0,0 +1,265 @@
+//! A list of moves that does not allocate.
+
+use std::{fmt::Debug, ops::Index};
+
+use super::board::Move;
+
+/// no legal chess position has more than 218 moves
+pub const MAX_MOVES: usize = 256;
+
+/// a fixed capacity list of moves with a score for each, kept on the stack
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    scores: [i32; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    /// an empty list
+    pub fn new() -> Self {
+        MoveList {
+            moves: [Move::NULL; MAX_MOVES],
+            scores: [0; MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    /// adds a move with score 0. pushing past MAX_MOVES is a bug
+    pub fn push(&mut self, m: Move) {
+        debug_assert!(self.len < MAX_MOVES);
+        self.moves[self.len] = m;
+        self.scores[self.len] = 0;
+        self.len += 1;
+    }
+
+    /// the number of moves
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// whether there are no moves
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// removes every move
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// whether `m` is in the list
+    pub fn contains(&self, m: &Move) -> bool {
+        self.iter().any(|x| x == m)
+    }
+
+    /// the moves, in order
+    pub fn iter(&self) -> std::slice::Iter<'_, Move> {
+        self.moves[..self.len].iter()
+    }
+
+    /// the score of the move at `index`
+    pub fn score(&self, index: usize) -> i32 {
+        self.scores[index]
+    }
+
+    /// sets the score of the move at `index`
+    pub fn set_score(&mut self, index: usize, score: i32) {
+        self.scores[index] = score;
+    }
+
+    /// swaps two moves, and their scores
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.moves.swap(a, b);
+        self.scores.swap(a, b);
+    }
+
+    /// keeps the moves for which `keep` returns true, preserving their order
+    pub fn retain(&mut self, mut keep: impl FnMut(&Move) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if keep(&self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                self.scores[kept] = self.scores[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+
+    /// highest score first, ties keep generation order
+    pub fn sort_by_score(&mut self) {
+        for i in 1..self.len {
+            let mut j = i;
+            while j > 0 && self.scores[j - 1] < self.scores[j] {
+                self.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+    }
+
+    /// moves the highest scored move in start.. to start and returns it,
+    /// so a search that cuts off early never pays for a full sort
+    pub fn pick_best(&mut self, start: usize) -> Move {
+        let mut best = start;
+        for i in start + 1..self.len {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.swap(start, best);
+        self.moves[start]
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<usize> for MoveList {
+    type Output = Move;
+
+    fn index(&self, index: usize) -> &Move {
+        &self.moves[..self.len][index]
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter {
+            list: self,
+            index: 0,
+        }
+    }
+}
+
+/// the moves of a MoveList, by value
+pub struct IntoIter {
+    list: MoveList,
+    index: usize,
+}
+
+impl Iterator for IntoIter {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        if self.index < self.list.len {
+            self.index += 1;
+            Some(self.list.moves[self.index - 1])
+        } else {
+            None
+        }
+    }
+}
+
+impl Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MoveList, MAX_MOVES};
+    use crate::game::board::{Move, QUIET};
+    use crate::game::square::Square;
+
+    // distinct moves, none of them null
+    fn list(len: usize) -> MoveList {
+        let mut moves = MoveList::new();
+        for i in 0..len {
+            moves.push(Move::new(Square::from_index(i % 64), Square::from_index(i / 64 + 8), QUIET));
+        }
+        moves
+    }
+
+    #[test]
+    fn push_to_capacity() {
+        let moves = list(MAX_MOVES);
+        assert_eq!(moves.len(), MAX_MOVES);
+        let last = moves[MAX_MOVES - 1];
+        assert_eq!((last.from().index(), last.to().index()), (63, 11));
+        assert!(MoveList::new().is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn push_past_capacity() {
+        list(MAX_MOVES).push(Move::NULL);
+    }
+
+    #[test]
+    fn retain() {
+        let mut moves = list(10);
+        for i in 0..10 {
+            moves.set_score(i, i as i32 * 10);
+        }
+        moves.retain(|m| m.from().index() % 3 == 0);
+        assert_eq!(moves.len(), 4);
+        for (i, from) in [0, 3, 6, 9].into_iter().enumerate() {
+            assert_eq!(moves[i].from().index(), from);
+            // scores move with their moves
+            assert_eq!(moves.score(i), from as i32 * 10);
+        }
+        moves.retain(|_| false);
+        assert!(moves.is_empty());
+    }
+
+    #[test]
+    fn sort_by_score() {
+        let mut moves = list(6);
+        for (i, score) in [5, -1, 7, 5, 0, 7].into_iter().enumerate() {
+            moves.set_score(i, score);
+        }
+        moves.sort_by_score();
+        // ties stay in the order they were generated
+        let order: Vec<usize> = moves.iter().map(|m| m.from().index()).collect();
+        assert_eq!(order, [2, 5, 0, 3, 4, 1]);
+        let scores: Vec<i32> = (0..moves.len()).map(|i| moves.score(i)).collect();
+        assert_eq!(scores, [7, 7, 5, 5, 0, -1]);
+    }
+
+    #[test]
+    fn pick_best() {
+        let mut moves = list(5);
+        for (i, score) in [3, 9, -2, 9, 4].into_iter().enumerate() {
+            moves.set_score(i, score);
+        }
+        let picked: Vec<(usize, i32)> = (0..moves.len())
+            .map(|i| {
+                let m = moves.pick_best(i);
+                assert_eq!(moves[i], m);
+                (m.from().index(), moves.score(i))
+            })
+            .collect();
+        assert_eq!(picked, [(1, 9), (3, 9), (4, 4), (0, 3), (2, -2)]);
+    }
+
+    #[test]
+    fn iterators() {
+        let moves = list(70);
+        let by_ref: Vec<Move> = (&moves).into_iter().copied().collect();
+        let by_value: Vec<Move> = moves.into_iter().collect();
+        assert_eq!(by_ref.len(), 70);
+        assert_eq!(by_ref, by_value);
+        assert_eq!(by_value[65], moves[65]);
+        assert!(moves.contains(&moves[69]));
+        assert!(!moves.contains(&Move::NULL));
+        assert_eq!(MoveList::new().into_iter().next(), None);
+    }
+}
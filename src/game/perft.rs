@@ -1,5 +1,9 @@
+//! Move generation testing by counting the nodes of the move tree.
+
 use super::{board::Board, movegen::generate_legal_moves};
 
+/// counts the leaf nodes of the legal move tree `depth` plies deep. with
+/// `depth_from_root` 0 the count below every root move is printed as well
 pub fn go(depth: u8, board: &mut Board, depth_from_root: u8) -> u64 {
     if depth == 1 {
         let legal_moves = generate_legal_moves(board);
@@ -25,4 +29,186 @@ pub fn go(depth: u8, board: &mut Board, depth_from_root: u8) -> u64 {
     }
 
     count
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::go;
+    use crate::game::board::{Board, Move, PieceTypes, STARTPOS};
+    use crate::game::movegen::generate_legal_moves;
+    use crate::game::square::Rank;
+
+    fn assert_perft(fen: &str, counts: &[u64]) {
+        let mut board = Board::new(fen);
+        for (depth, &count) in counts.iter().enumerate() {
+            assert_eq!(go(depth as u8 + 1, &mut board, 1), count, "{} depth {}", fen, depth + 1);
+        }
+    }
+
+    #[test]
+    fn standard_positions() {
+        assert_perft(STARTPOS, &[20, 400, 8902, 197281]);
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        );
+        assert_perft("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238]);
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    #[test]
+    fn chess960_positions() {
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[21, 528, 12189, 326672],
+        );
+        assert_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[21, 807, 18002, 667366],
+        );
+        assert_perft(
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            &[20, 479, 10471, 273318],
+        );
+        assert_perft(
+            "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+            &[22, 593, 13440, 382958],
+        );
+        assert_perft(
+            "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+            &[28, 1120, 31058, 1171749],
+        );
+    }
+
+    #[test]
+    fn chess960_fen_round_trip() {
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        assert_eq!(Board::new(fen).shredder_fen(), fen);
+        assert_eq!(
+            Board::new(fen).fen(),
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+        );
+        assert_eq!(Board::new(STARTPOS).fen(), STARTPOS);
+
+        // a rook that is not the outermost one has to be named by its file
+        let fen = "1r2k1r1/8/8/8/8/8/8/RR2K3 w Bk - 0 1";
+        assert_eq!(Board::new(fen).fen(), fen);
+        assert_eq!(Board::new(fen).shredder_fen(), "1r2k1r1/8/8/8/8/8/8/RR2K3 w Bg - 0 1");
+    }
+
+    #[test]
+    fn mirrored_positions() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        ] {
+            let mut board = Board::new(fen);
+            let count = go(3, &mut board, 1);
+            assert_eq!(go(3, &mut board.mirror(), 1), count, "{} mirrored", fen);
+            if let Some(mut flipped) = board.flip_horizontal() {
+                assert_eq!(go(3, &mut flipped, 1), count, "{} flipped", fen);
+            }
+        }
+    }
+
+    // captures (en passant included), en passant captures, castles and promotions among the
+    // moves at the last ply, as the published perft statistics count them
+    #[derive(Debug, Default, PartialEq)]
+    struct MoveKinds {
+        captures: u64,
+        ep: u64,
+        castles: u64,
+        promotions: u64,
+    }
+
+    fn count_kinds(depth: u8, board: &mut Board, kinds: &mut MoveKinds) {
+        for m in generate_legal_moves(board) {
+            assert_flags_match(board, &m);
+            if depth == 1 {
+                kinds.captures += u64::from(m.is_capture());
+                kinds.ep += u64::from(m.is_ep());
+                kinds.castles += u64::from(m.is_castle());
+                kinds.promotions += u64::from(m.is_promotion());
+            } else {
+                let undo = board.push(&m);
+                count_kinds(depth - 1, board, kinds);
+                undo(board);
+            }
+        }
+    }
+
+    // the flags say what the board says about the move
+    fn assert_flags_match(board: &Board, m: &Move) {
+        let piece = board.board[m.from().index()];
+        let target = board.board[m.to().index()];
+        let pawn = piece.piece_type == PieceTypes::Pawn;
+        let ep = pawn && Some(m.to()) == board.enpassant_square;
+        let enemy = target.piece_type != PieceTypes::Empty && target.color != piece.color;
+
+        assert_eq!(m.is_capture(), enemy || ep, "{:?} in {}", m, board.fen());
+        assert_eq!(m.is_ep(), ep, "{:?} in {}", m, board.fen());
+        assert_eq!(
+            m.is_castle(),
+            piece.piece_type == PieceTypes::King
+                && target.piece_type == PieceTypes::Rook
+                && target.color == piece.color,
+            "{:?} in {}",
+            m,
+            board.fen()
+        );
+        assert_eq!(
+            m.is_double_push(),
+            pawn && m.from().rank().distance(m.to().rank()) == 2,
+            "{:?} in {}",
+            m,
+            board.fen()
+        );
+        assert_eq!(
+            m.is_promotion(),
+            pawn && m.to().rank().relative_to(piece.color) == Rank::EIGHTH,
+            "{:?} in {}",
+            m,
+            board.fen()
+        );
+    }
+
+    fn assert_kinds(fen: &str, depth: u8, captures: u64, ep: u64, castles: u64, promotions: u64) {
+        let mut kinds = MoveKinds::default();
+        count_kinds(depth, &mut Board::new(fen), &mut kinds);
+        let expected = MoveKinds {
+            captures,
+            ep,
+            castles,
+            promotions,
+        };
+        assert_eq!(kinds, expected, "{} depth {}", fen, depth);
+    }
+
+    #[test]
+    fn move_flags() {
+        assert_kinds(STARTPOS, 4, 1576, 0, 0, 0);
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_kinds(kiwipete, 1, 8, 0, 2, 0);
+        assert_kinds(kiwipete, 2, 351, 1, 91, 0);
+        assert_kinds(kiwipete, 3, 17102, 45, 3162, 0);
+        assert_kinds("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 3348, 123, 0, 0);
+        let position4 = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        assert_kinds(position4, 2, 87, 0, 6, 48);
+        assert_kinds(position4, 3, 1021, 4, 0, 120);
+        // no published statistics, but the flags still have to match the board
+        let mut kinds = MoveKinds::default();
+        let position5 = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+        count_kinds(3, &mut Board::new(position5), &mut kinds);
+    }
+}
@@ -0,0 +1,255 @@
+//! Text diagrams of a board for terminals and logs.
+
+use std::fmt::Write;
+
+use super::board::{castling_destinations, piece_char, Board, Move, Piece, PieceTypes};
+use super::color::Color;
+use super::square::{File, Rank, Square};
+
+const RESET: &str = "\x1b[0m";
+const LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+const LIGHT_HIGHLIGHT: &str = "\x1b[48;5;186m";
+const DARK_HIGHLIGHT: &str = "\x1b[48;5;143m";
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+
+/// how Board::render draws the board, plain letters by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderOptions {
+    /// chess symbols instead of letters
+    pub unicode: bool,
+    /// ansi colours for the squares and pieces, for terminals
+    pub color: bool,
+    /// highlighted with the square colours, or with brackets when colours are off
+    pub last_move: Option<Move>,
+}
+
+impl Board {
+    /// the board with white at the bottom, rank and file labels and a line
+    /// with the state a fen would have, e.g.
+    ///
+    /// ```text
+    /// 8  r  n  b  q  k  b  n  r
+    /// ...
+    ///    a  b  c  d  e  f  g  h
+    /// white to move, castling KQkq, en passant -, halfmoves 0, move 1
+    /// ```
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let highlighted = highlighted_squares(options.last_move);
+
+        let mut out = String::new();
+        for rank in (0..8).rev().filter_map(Rank::new) {
+            write!(out, "{} ", rank).unwrap();
+            for file in File::all() {
+                let square = Square::new(file, rank);
+                let piece = self.board[square.index()];
+                let highlight = highlighted.contains(&Some(square));
+                out.push_str(&render_square(square, piece, highlight, options));
+            }
+            out.push('\n');
+        }
+
+        out.push_str("  ");
+        for file in File::all() {
+            write!(out, " {} ", file).unwrap();
+        }
+        out.push('\n');
+
+        let enpassant = self
+            .enpassant_square
+            .map_or_else(|| "-".to_string(), |square| square.to_string());
+        write!(
+            out,
+            "{} to move, castling {}, en passant {}, halfmoves {}, move {}",
+            if self.side_to_move == Color::White {
+                "white"
+            } else {
+                "black"
+            },
+            self.castling_field(false),
+            enpassant,
+            self.halfmoves,
+            self.fullmoves
+        )
+        .unwrap();
+
+        out
+    }
+}
+
+// where the moving piece came from and went to, the king's squares for castling
+fn highlighted_squares(last_move: Option<Move>) -> [Option<Square>; 2] {
+    match last_move {
+        Some(m) if m.is_null() => [None, None],
+        Some(m) if m.is_castle() => [
+            Some(m.from()),
+            Some(castling_destinations(m.from(), m.to()).0),
+        ],
+        Some(m) => [Some(m.from()), Some(m.to())],
+        None => [None, None],
+    }
+}
+
+// every square is three characters wide so the labels line up
+fn render_square(square: Square, piece: Piece, highlight: bool, options: &RenderOptions) -> String {
+    let glyph = piece_glyph(piece, options.unicode);
+    if !options.color {
+        return if highlight {
+            format!("[{}]", glyph)
+        } else {
+            format!(" {} ", glyph)
+        };
+    }
+
+    // a1 is a dark square
+    let light = (square.file().index() + square.rank().index()) % 2 == 1;
+    let background = match (light, highlight) {
+        (true, false) => LIGHT_SQUARE,
+        (false, false) => DARK_SQUARE,
+        (true, true) => LIGHT_HIGHLIGHT,
+        (false, true) => DARK_HIGHLIGHT,
+    };
+    let foreground = if piece.color == Color::White {
+        WHITE_PIECE
+    } else {
+        BLACK_PIECE
+    };
+    let glyph = if piece.piece_type == PieceTypes::Empty {
+        ' '
+    } else {
+        glyph
+    };
+    format!("{}{} {} {}", background, foreground, glyph, RESET)
+}
+
+fn piece_glyph(piece: Piece, unicode: bool) -> char {
+    let white = piece.color == Color::White;
+    if unicode {
+        return match (piece.piece_type, white) {
+            (PieceTypes::Empty, _) => '·',
+            (PieceTypes::Pawn, true) => '♙',
+            (PieceTypes::Knight, true) => '♘',
+            (PieceTypes::Bishop, true) => '♗',
+            (PieceTypes::Rook, true) => '♖',
+            (PieceTypes::Queen, true) => '♕',
+            (PieceTypes::King, true) => '♔',
+            (PieceTypes::Pawn, false) => '♟',
+            (PieceTypes::Knight, false) => '♞',
+            (PieceTypes::Bishop, false) => '♝',
+            (PieceTypes::Rook, false) => '♜',
+            (PieceTypes::Queen, false) => '♛',
+            (PieceTypes::King, false) => '♚',
+        };
+    }
+    if piece.piece_type == PieceTypes::Empty {
+        return '.';
+    }
+    let c = piece_char(piece.piece_type);
+    if white {
+        c
+    } else {
+        c.to_ascii_lowercase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        RenderOptions, BLACK_PIECE, DARK_HIGHLIGHT, DARK_SQUARE, LIGHT_HIGHLIGHT, LIGHT_SQUARE,
+        RESET, WHITE_PIECE,
+    };
+    use crate::game::board::{Board, Move, STARTPOS};
+
+    #[test]
+    fn letters() {
+        let board = Board::new("r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 3 20");
+        let out = board.render(&RenderOptions::default());
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "8  r  .  .  .  k  .  .  r ");
+        assert_eq!(lines[3], "5  .  .  .  p  P  .  .  . ");
+        assert_eq!(lines[7], "1  R  .  .  .  K  .  .  R ");
+        assert_eq!(lines[8], "   a  b  c  d  e  f  g  h ");
+        assert_eq!(
+            lines[9],
+            "white to move, castling Kq, en passant d6, halfmoves 3, move 20"
+        );
+    }
+
+    #[test]
+    fn unicode() {
+        let options = RenderOptions {
+            unicode: true,
+            ..RenderOptions::default()
+        };
+        let out = Board::new(STARTPOS).render(&options);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜ ");
+        assert_eq!(lines[1], "7  ♟  ♟  ♟  ♟  ♟  ♟  ♟  ♟ ");
+        assert_eq!(lines[4], "4  ·  ·  ·  ·  ·  ·  ·  · ");
+        assert_eq!(lines[6], "2  ♙  ♙  ♙  ♙  ♙  ♙  ♙  ♙ ");
+        assert_eq!(lines[7], "1  ♖  ♘  ♗  ♕  ♔  ♗  ♘  ♖ ");
+    }
+
+    #[test]
+    fn last_move() {
+        let mut board = Board::new(STARTPOS);
+        let m = Move::from_uci("e2e4", &board).unwrap();
+        let _ = board.try_push(&m).unwrap();
+        let options = RenderOptions {
+            last_move: Some(m),
+            ..RenderOptions::default()
+        };
+        let out = board.render(&options);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[4], "4  .  .  .  . [P] .  .  . ");
+        assert_eq!(lines[6], "2  P  P  P  P [.] P  P  P ");
+        assert_eq!(
+            lines[9],
+            "black to move, castling KQkq, en passant e3, halfmoves 0, move 1"
+        );
+
+        // castling highlights where the king went, not the rook
+        let mut board = Board::new("4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        let castle = Move::from_uci("e1g1", &board).unwrap();
+        let _ = board.try_push(&castle).unwrap();
+        let options = RenderOptions {
+            last_move: Some(castle),
+            ..RenderOptions::default()
+        };
+        let out = board.render(&options);
+        assert_eq!(out.lines().nth(7).unwrap(), "1  .  .  .  . [.] R [K] . ");
+    }
+
+    #[test]
+    fn color() {
+        let mut board = Board::new("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        let m = Move::from_uci("e1e2", &board).unwrap();
+        let _ = board.try_push(&m).unwrap();
+        let options = RenderOptions {
+            color: true,
+            last_move: Some(m),
+            ..RenderOptions::default()
+        };
+        let out = board.render(&options);
+        let lines: Vec<&str> = out.lines().collect();
+
+        // a8 is light, and each square starts with its background and the piece colour
+        let square = |background: &str, foreground: &str, glyph: char| {
+            format!("{}{} {} {}", background, foreground, glyph, RESET)
+        };
+        let empty_light = square(LIGHT_SQUARE, BLACK_PIECE, ' ');
+        let empty_dark = square(DARK_SQUARE, BLACK_PIECE, ' ');
+        assert!(lines[0].starts_with(&format!("8 {}{}", empty_light, empty_dark)));
+        assert!(lines[0].contains(&square(LIGHT_SQUARE, BLACK_PIECE, 'k')));
+
+        // e1 is dark and e2 light, both highlighted
+        assert!(lines[7].contains(&square(DARK_HIGHLIGHT, BLACK_PIECE, ' ')));
+        assert!(lines[6].contains(&square(LIGHT_HIGHLIGHT, WHITE_PIECE, 'K')));
+        assert_eq!(lines[6].matches(RESET).count(), 8);
+        assert!(!out.contains("[K]"));
+
+        let plain = Board::new(STARTPOS).render(&RenderOptions::default());
+        assert!(!plain.contains('\x1b'));
+    }
+}
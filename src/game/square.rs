@@ -0,0 +1,235 @@
+//! Squares, files and ranks.
+
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
+};
+
+use super::color::Color;
+
+/// index 0 is a8 and 63 is h1, the same order as the board array
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(u8);
+
+/// 0 is the a file
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct File(u8);
+
+/// 0 is the first rank, where white starts
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Rank(u8);
+
+/// why a string is not a square
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseSquareError(pub String);
+
+impl Display for ParseSquareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid square: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl Square {
+    /// the square at `index`, 0 to 63
+    pub fn from_index(index: usize) -> Square {
+        debug_assert!(index < 64);
+        Square(index as u8)
+    }
+
+    /// the square on `file` and `rank`
+    pub fn new(file: File, rank: Rank) -> Square {
+        Square((7 - rank.0) * 8 + file.0)
+    }
+
+    /// every square, a8 first
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+
+    /// 0 for a8 up to 63 for h1
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// a bitboard with just this square set
+    pub fn bit(self) -> u64 {
+        1 << self.0
+    }
+
+    /// the file of the square
+    pub fn file(self) -> File {
+        File(self.0 % 8)
+    }
+
+    /// the rank of the square
+    pub fn rank(self) -> Rank {
+        Rank(7 - self.0 / 8)
+    }
+
+    /// positive rank offsets go towards the eighth rank, positive file offsets towards the h file
+    pub fn offset(self, file_offset: i8, rank_offset: i8) -> Option<Square> {
+        let file = File::new(self.file().0 as i8 + file_offset)?;
+        let rank = Rank::new(self.rank().0 as i8 + rank_offset)?;
+        Some(Square::new(file, rank))
+    }
+
+    /// one rank closer to the opponent of `color`, the way its pawns move
+    pub fn forward(self, color: Color) -> Option<Square> {
+        self.offset(0, if color.is_white() { 1 } else { -1 })
+    }
+
+    /// one rank closer to `color`'s own side, the square a pawn came from
+    pub fn backward(self, color: Color) -> Option<Square> {
+        self.offset(0, if color.is_white() { -1 } else { 1 })
+    }
+
+    /// the same square seen from the other side of the board, a1 becomes a8
+    pub fn flip(self) -> Square {
+        Square(self.0 ^ 56)
+    }
+
+    /// mirrored between the queen and king side, a1 becomes h1
+    pub fn flip_horizontal(self) -> Square {
+        Square(self.0 ^ 7)
+    }
+
+    /// how many king moves it takes to get from one square to the other
+    pub fn distance(self, other: Square) -> u8 {
+        self.file().distance(other.file()).max(self.rank().distance(other.rank()))
+    }
+}
+
+impl File {
+    /// the c file
+    pub const C: File = File(2);
+    /// the d file
+    pub const D: File = File(3);
+    /// the f file
+    pub const F: File = File(5);
+    /// the g file
+    pub const G: File = File(6);
+
+    /// every file, the a file first
+    pub fn all() -> impl Iterator<Item = File> {
+        (0..8).map(File)
+    }
+
+    /// the file with index `file` from 0 for the a file, None off the board
+    pub fn new(file: i8) -> Option<File> {
+        (0..8).contains(&file).then_some(File(file as u8))
+    }
+
+    /// 0 for the a file up to 7 for the h file
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// how many files apart two files are
+    pub fn distance(self, other: File) -> u8 {
+        self.0.abs_diff(other.0)
+    }
+}
+
+impl Rank {
+    /// the first rank, where white's pieces start
+    pub const FIRST: Rank = Rank(0);
+    /// the second rank, where white's pawns start
+    pub const SECOND: Rank = Rank(1);
+    /// the sixth rank, where the en passant square is when white is to move
+    pub const SIXTH: Rank = Rank(5);
+    /// the seventh rank, where black's pawns start
+    pub const SEVENTH: Rank = Rank(6);
+    /// the eighth rank, where black's pieces start
+    pub const EIGHTH: Rank = Rank(7);
+
+    /// the rank with index `rank` from 0 for the first rank, None off the board
+    pub fn new(rank: i8) -> Option<Rank> {
+        (0..8).contains(&rank).then_some(Rank(rank as u8))
+    }
+
+    /// 0 for the first rank up to 7 for the eighth
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// how many ranks apart two ranks are
+    pub fn distance(self, other: Rank) -> u8 {
+        self.0.abs_diff(other.0)
+    }
+
+    /// the rank as `color` counts it, so the second rank is black's seventh
+    pub fn relative_to(self, color: Color) -> Rank {
+        if color.is_white() {
+            self
+        } else {
+            self.flip()
+        }
+    }
+
+    /// the same rank seen from the other side, the first rank becomes the eighth
+    pub fn flip(self) -> Rank {
+        Rank(7 - self.0)
+    }
+}
+
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    // a file a-h followed by a rank 1-8, e.g. e4
+    fn from_str(s: &str) -> Result<Square, ParseSquareError> {
+        match s.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Ok(Square::new(File(file - b'a'), Rank(rank - b'1')))
+            }
+            _ => Err(ParseSquareError(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for File {
+    type Err = ParseSquareError;
+
+    fn from_str(s: &str) -> Result<File, ParseSquareError> {
+        match s.as_bytes() {
+            &[file @ b'a'..=b'h'] => Ok(File(file - b'a')),
+            _ => Err(ParseSquareError(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for Rank {
+    type Err = ParseSquareError;
+
+    fn from_str(s: &str) -> Result<Rank, ParseSquareError> {
+        match s.as_bytes() {
+            &[rank @ b'1'..=b'8'] => Ok(Rank(rank - b'1')),
+            _ => Err(ParseSquareError(s.to_string())),
+        }
+    }
+}
+
+impl Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.file(), self.rank())
+    }
+}
+
+impl Debug for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Display for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", (b'a' + self.0) as char)
+    }
+}
+
+impl Display for Rank {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0 + 1)
+    }
+}
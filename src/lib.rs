@@ -0,0 +1,30 @@
+//! A chess engine: board representation, move generation, perft, evaluation,
+//! alpha-beta search and a UCI front end.
+//!
+//! ```
+//! use rustchess::{perft, Board, Move, STARTPOS};
+//!
+//! let mut board = Board::new(STARTPOS);
+//! let m = Move::from_uci("e2e4", &board).unwrap();
+//! board.try_push(&m).unwrap();
+//! assert_eq!(board.fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+//! assert_eq!(perft::go(2, &mut board, 1), 600);
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod engine;
+pub mod game;
+pub mod uci;
+
+pub use engine::eval::{evaluate, Score};
+pub use engine::search::{absearch, iterative_deepening, EvalResult, PvLine, SearchParams, SearchState};
+pub use engine::transposition::TranspositionTable;
+pub use game::board::{Board, IllegalMoveError, Move, ParseFenError, ParseMoveError, Piece, PieceTypes, STARTPOS};
+pub use game::color::Color;
+pub use game::encoding::{DecodeError, EncodedBoard};
+pub use game::movegen::{generate_legal_moves, in_check};
+pub use game::movelist::MoveList;
+pub use game::perft;
+pub use game::render::RenderOptions;
+pub use game::square::{File, Rank, Square};
@@ -1,38 +1,33 @@
-use game::board::{Board, STARTPOS, Move, PieceTypes};
-use engine::search::{absearch, EvalResult};
+use rustchess::engine::hash::generate_hash;
+use rustchess::engine::search;
+use rustchess::game::movegen::generate_legal_moves;
+use rustchess::uci::uci;
+use rustchess::{
+    absearch, perft, Board, Move, RenderOptions, SearchParams, SearchState, TranspositionTable,
+};
 use std::io;
+use std::sync::atomic::AtomicBool;
 use std::time::Instant;
 
-use crate::engine::hash::generate_hash;
-use crate::engine::search::{self, REALLY_BIG_NUMBER, REALLY_SMALL_NUMBER};
-use crate::game::movegen::generate_legal_moves;
-use crate::game::perft;
-use crate::uci::uci;
-
-mod game;
-mod engine;
-mod uci;
-
 fn main() -> Result<(), ()>{
     let mut input = String::new();
 
     io::stdin().read_line(&mut input).expect("Failed to read line");
 
-    match &*input.trim() {
-        "cli" => {return cli()},
-        "uci" => {return uci()},
+    match input.trim() {
+        "cli" => cli(),
+        "uci" => uci().map_err(|_| ()),
         _ => {
-            println!("\"{}\" is not supported.", &*input.trim());
-            return Err(());
+            println!("\"{}\" is not supported.", input.trim());
+            Err(())
         },
     }
 }
 
 fn cli() -> Result<(), ()> {
     let mut board = Board::new("8/4k3/8/3K4/4P3/8/8/8 w - - 0 1");
-    // println!("{:?}", board.zobrist_table);
-    let mut undo = board.push(&Move::parse_from("e2e4"));
-    undo(&mut board);
+    let mut undo: Box<dyn Fn(&mut Board)> = Box::new(|_: &mut Board| ());
+    let mut render_options = RenderOptions::default();
 
     loop {
         board.hash = generate_hash(&board);
@@ -42,36 +37,57 @@ fn cli() -> Result<(), ()> {
 
         io::stdin().read_line(&mut input).expect("Failed to read line");
 
-        if &*input.trim() == "exit" {
+        if input.trim() == "exit" {
             break;
-        } else if &*input.trim() == "go" {
+        } else if input.trim() == "go" {
             computer_move = true;
-        } else if &*input.trim() == "perft" {
+        } else if input.trim() == "perft" {
             let start = Instant::now();
             println!("{}", perft::go(5, &mut board, 0));
             println!("finished in {:.2?}", start.elapsed());
-        } else if &*input.trim() == "undo" {
+        } else if input.trim().starts_with("render") {
+            // render [unicode] [color], with neither for plain letters
+            let options: Vec<&str> = input.split_whitespace().skip(1).collect();
+            render_options.unicode = options.contains(&"unicode");
+            render_options.color = options.contains(&"color");
+            println!("{}", board.render(&render_options));
+        } else if input.trim() == "undo" {
             undo(&mut board);
-            println!("{}", board);
+            render_options.last_move = None;
+            println!("{}", board.render(&render_options));
             println!("{:#066b}", board.hash);
         } else {
-            undo = board.push(&Move::parse_from(&*input.trim()));
-            println!("{}", board);
-            println!("{:#066b}", board.hash);
+            match Move::from_uci(input.trim(), &board) {
+                Ok(m) => match board.try_push(&m) {
+                    Ok(undo_move) => {
+                        undo = Box::new(undo_move);
+                        render_options.last_move = Some(m);
+                        println!("{}", board.render(&render_options));
+                        println!("{:#066b}", board.hash);
+                    }
+                    Err(e) => println!("{}", e),
+                },
+                Err(e) => println!("{}", e),
+            }
         }   
         
         if computer_move {
-            let mut eval_result = EvalResult{best_move: Move{from: 0, to: 0, promotion: PieceTypes::Empty}};
+            let tt = TranspositionTable::default();
+            let stop = AtomicBool::new(false);
+            let mut state = SearchState::new(SearchParams::default(), &tt, &stop);
 
             let start = Instant::now();
             // search(4, &mut board, &mut eval_result, 0);
-            println!("{}", absearch(16, &mut board, search::REALLY_SMALL_NUMBER * 10.0, search::REALLY_BIG_NUMBER * 10.0, &mut eval_result, 0, &mut Vec::new()));
+            println!("{}", absearch(16, &mut board, search::REALLY_SMALL_NUMBER * 10, search::REALLY_BIG_NUMBER * 10, &mut state, 0));
             let elapsed = start.elapsed();
-            undo = board.push(&eval_result.best_move);
+            let san = board.san(&state.result.best_move);
+            undo = Box::new(board.push(&state.result.best_move));
+            render_options.last_move = Some(state.result.best_move);
 
-            println!("{}finished in {:.2?}", board, elapsed);
+            println!("{}", board.render(&render_options));
+            println!("finished in {:.2?}", elapsed);
             println!("{:#066b}", board.hash);
-            println!("{}", eval_result.best_move.uci());
+            println!("{} ({})", state.result.best_move.uci(), san);
         }
     }
     Ok(())
@@ -1,3 +1,246 @@
-pub fn uci() -> Result<(), ()> {
+//! The Universal Chess Interface front end.
+
+use std::io;
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::engine::{
+    self,
+    bench::BENCH_DEPTH,
+    eval::Score,
+    search::{iterative_deepening, SearchParams, MATE_BOUND, REALLY_BIG_NUMBER},
+    transposition::TranspositionTable,
+};
+use crate::game::board::{Board, Move, STARTPOS};
+
+const DEFAULT_DEPTH: u8 = 4;
+
+/// runs the engine as a uci engine on stdin and stdout until `quit`
+pub fn uci() -> io::Result<()> {
+    // main has already read the "uci" that got us here
+    identify();
+
+    let mut board = Board::new(STARTPOS);
+    let mut chess960 = false;
+    let mut params = SearchParams::default();
+    let tt = TranspositionTable::default();
+
+    for line in io::stdin().lines() {
+        let line = line?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.first().copied() {
+            Some("uci") => identify(),
+            Some("isready") => println!("readyok"),
+            Some("setoption") => match parse_option(&tokens[1..]) {
+                Some((name, value)) if name == "UCI_Chess960" => {
+                    chess960 = value == "true";
+                    board.chess960 = chess960;
+                }
+                Some((name, value)) => {
+                    if let Err(e) = set_search_option(&mut params, &name, &value) {
+                        println!("info string {}", e);
+                    }
+                }
+                None => (),
+            },
+            Some("ucinewgame") => {
+                board = Board::new(STARTPOS);
+                board.chess960 = chess960;
+                tt.clear();
+            }
+            Some("position") => match parse_position(&tokens[1..], chess960) {
+                Ok(new_board) => board = new_board,
+                Err(e) => println!("info string {}", e),
+            },
+            Some("go") => go(&mut board, &tokens[1..], params, &tt),
+            Some("bench") => bench(&tokens[1..], params),
+            Some("quit") => break,
+            _ => (),
+        }
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
+
+fn identify() {
+    println!("id name rustchess");
+    println!("option name UCI_Chess960 type check default false");
+    println!("option name Threads type spin default 1 min 1 max 256");
+    println!("option name MultiPV type spin default 1 min 1 max 256");
+    println!("option name NullMove type check default true");
+    println!("option name LateMoveReductions type check default true");
+    println!("option name Extensions type check default true");
+    println!("option name QuietMoveOrdering type check default true");
+    println!("option name ReverseFutility type check default true");
+    println!("option name ReverseFutilityMargin type spin default 80 min 0 max 1000");
+    println!("option name Razoring type check default true");
+    println!("option name RazoringMargin type spin default 200 min 0 max 1000");
+    println!("option name Futility type check default true");
+    println!("option name FutilityMargin type spin default 100 min 0 max 1000");
+    println!("uciok");
+}
+
+// setoption name <name> value <value>
+fn parse_option(tokens: &[&str]) -> Option<(String, String)> {
+    let value_index = tokens.iter().position(|&token| token == "value")?;
+    if tokens.first() != Some(&"name") {
+        return None;
+    }
+    Some((tokens[1..value_index].join(" "), tokens[value_index + 1..].join(" ")))
+}
+
+fn set_search_option(params: &mut SearchParams, name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "Threads" => params.threads = parse_value::<usize>(name, value)?.max(1),
+        "MultiPV" => params.multi_pv = parse_value::<usize>(name, value)?.max(1),
+        "NullMove" => params.null_move = value == "true",
+        "LateMoveReductions" => params.late_move_reductions = value == "true",
+        "Extensions" => params.extensions = value == "true",
+        "QuietMoveOrdering" => params.quiet_move_ordering = value == "true",
+        "ReverseFutility" => params.reverse_futility_pruning = value == "true",
+        "ReverseFutilityMargin" => params.reverse_futility_margin = parse_value(name, value)?,
+        "Razoring" => params.razoring = value == "true",
+        "RazoringMargin" => params.razoring_margin = parse_value(name, value)?,
+        "Futility" => params.futility_pruning = value == "true",
+        "FutilityMargin" => params.futility_margin = parse_value(name, value)?,
+        _ => return Err(format!("unknown option {}", name)),
+    }
+    Ok(())
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {} for {}", value, name))
+}
+
+// position [startpos | fen <fen>] [moves <move>...]
+fn parse_position(tokens: &[&str], chess960: bool) -> Result<Board, String> {
+    let moves_index = tokens
+        .iter()
+        .position(|&token| token == "moves")
+        .unwrap_or(tokens.len());
+
+    let mut board = match tokens.first().copied() {
+        Some("startpos") => Board::new(STARTPOS),
+        Some("fen") => tokens[1..moves_index]
+            .join(" ")
+            .parse::<Board>()
+            .map_err(|e| e.to_string())?,
+        _ => return Err(String::from("expected startpos or fen")),
+    };
+    // castling moves are written differently in chess960
+    board.chess960 = chess960;
+
+    for token in tokens.iter().skip(moves_index + 1) {
+        let m = Move::from_uci(token, &board).map_err(|e| e.to_string())?;
+        if let Err(e) = board.try_push(&m) {
+            return Err(e.to_string());
+        }
+    }
+
+    Ok(board)
+}
+
+// go [depth <plies>]
+fn go(board: &mut Board, tokens: &[&str], params: SearchParams, tt: &TranspositionTable) {
+    let depth = tokens
+        .iter()
+        .position(|&token| token == "depth")
+        .and_then(|i| tokens.get(i + 1))
+        .and_then(|depth| depth.parse().ok())
+        .unwrap_or(DEFAULT_DEPTH);
+
+    let chess960 = board.chess960;
+    let eval_result = iterative_deepening(board, depth, params, tt, |eval_result| {
+        for (i, line) in eval_result.lines.iter().enumerate() {
+            let pv: Vec<String> = line.pv.iter().map(|m| m.to_uci(chess960)).collect();
+            println!(
+                "info depth {} multipv {} score {} nodes {} pv {}",
+                eval_result.depth,
+                i + 1,
+                uci_score(line.score),
+                eval_result.nodes,
+                pv.join(" ")
+            );
+        }
+    });
+
+    println!("bestmove {}", eval_result.best_move.to_uci(board.chess960));
+}
+
+// cp <centipawns>, or mate <moves> for a mate the search found, negative when the side to
+// move is the one getting mated
+fn uci_score(score: Score) -> String {
+    if score.abs() < MATE_BOUND {
+        return format!("cp {}", score);
+    }
+    let moves = (REALLY_BIG_NUMBER - score.abs() + 1) / 2;
+    format!("mate {}", if score > 0 { moves } else { -moves })
+}
+
+// bench [depth], not part of uci: searches the bench positions with the current options
+fn bench(tokens: &[&str], params: SearchParams) {
+    let depth = tokens
+        .first()
+        .and_then(|depth| depth.parse().ok())
+        .unwrap_or(BENCH_DEPTH);
+
+    let start = Instant::now();
+    let nodes = engine::bench::bench(depth, params, |fen, eval_result| {
+        println!("info string {} nodes {}", fen, eval_result.nodes);
+    });
+    let elapsed = start.elapsed();
+    println!(
+        "info string bench nodes {} time {} nps {}",
+        nodes,
+        elapsed.as_millis(),
+        (nodes as f64 / elapsed.as_secs_f64()) as u64
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_position, uci_score};
+    use crate::engine::search::{MATE_BOUND, REALLY_BIG_NUMBER, REALLY_SMALL_NUMBER};
+
+    fn parse(command: &str) -> Result<String, String> {
+        let tokens: Vec<&str> = command.split_whitespace().collect();
+        parse_position(&tokens, false).map(|board| board.fen())
+    }
+
+    #[test]
+    fn position() {
+        assert_eq!(
+            parse("startpos moves e2e4"),
+            Ok(String::from("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"))
+        );
+        assert_eq!(
+            parse("fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Ok(String::from("4k3/8/8/8/8/8/8/4K3 w - - 0 1"))
+        );
+
+        // errors instead of panics, and the caller keeps its board
+        assert!(parse("fen 8/8/8 w - - 0 1").is_err());
+        assert!(parse("fen 8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(parse("fen").is_err());
+        assert!(parse("startpos moves e2e5").is_err());
+        assert!(parse("startpos moves e2").is_err());
+        assert!(parse("somewhere").is_err());
+    }
+
+    #[test]
+    fn score() {
+        assert_eq!(uci_score(35), "cp 35");
+        assert_eq!(uci_score(-1200), "cp -1200");
+        assert_eq!(uci_score(MATE_BOUND - 1), format!("cp {}", MATE_BOUND - 1));
+        // mate in plies from the root, rounded up to whole moves
+        assert_eq!(uci_score(REALLY_BIG_NUMBER - 1), "mate 1");
+        assert_eq!(uci_score(REALLY_BIG_NUMBER - 3), "mate 2");
+        assert_eq!(uci_score(REALLY_BIG_NUMBER - 9), "mate 5");
+        assert_eq!(uci_score(REALLY_SMALL_NUMBER + 2), "mate -1");
+        assert_eq!(uci_score(REALLY_SMALL_NUMBER + 4), "mate -2");
+        assert_eq!(uci_score(-MATE_BOUND), "mate -128");
+    }
+}